@@ -0,0 +1,103 @@
+// compares Scheduler::SharedQueue with Scheduler::WorkStealing on many tiny jobs
+// run with: cargo bench --bench scheduler
+use multithread_server::{PoolConfig, Scheduler, ThreadPool};
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const JOBS: usize = 100_000;
+const ROUNDS: u32 = 5;
+
+fn main() {
+    for workers in [2, 8, 32] {
+        for scheduler in [Scheduler::SharedQueue, Scheduler::WorkStealing] {
+            let external = average(|| from_outside(scheduler, workers));
+            let nested = average(|| from_jobs(scheduler, workers));
+
+            println!(
+                "{:>2} workers {:<14} outside: {:>10.2?}  from jobs: {:>10.2?}",
+                workers,
+                format!("{:?}", scheduler),
+                external,
+                nested
+            );
+        }
+    }
+}
+
+fn average(mut run: impl FnMut() -> Duration) -> Duration {
+    (0..ROUNDS).map(|_| run()).sum::<Duration>() / ROUNDS
+}
+
+fn pool(scheduler: Scheduler, workers: usize) -> Arc<ThreadPool> {
+    let config = PoolConfig {
+        scheduler,
+        ..PoolConfig::new(workers)
+    };
+    Arc::new(ThreadPool::with_config(config).unwrap())
+}
+
+// the last job to finish reports back
+fn tiny_job(done: &Arc<AtomicUsize>, finished: &mpsc::Sender<()>) -> impl FnOnce() + Send {
+    let done = Arc::clone(done);
+    let finished = finished.clone();
+
+    move || {
+        black_box((0..16u64).sum::<u64>());
+        if done.fetch_add(1, Ordering::Relaxed) + 1 == JOBS {
+            finished.send(()).unwrap();
+        }
+    }
+}
+
+// every job submitted by the bench thread
+fn from_outside(scheduler: Scheduler, workers: usize) -> Duration {
+    let pool = pool(scheduler, workers);
+    let done = Arc::new(AtomicUsize::new(0));
+    let (finished, wait) = mpsc::channel();
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        pool.execute(tiny_job(&done, &finished));
+    }
+    wait.recv().unwrap();
+
+    start.elapsed()
+}
+
+// a few jobs fan out into the rest, like a recursive split
+fn from_jobs(scheduler: Scheduler, workers: usize) -> Duration {
+    const PARENTS: usize = 100;
+
+    let pool = pool(scheduler, workers);
+    let done = Arc::new(AtomicUsize::new(0));
+    let (finished, wait) = mpsc::channel();
+
+    let start = Instant::now();
+    for _ in 0..PARENTS {
+        let inner = Arc::clone(&pool);
+        let done = Arc::clone(&done);
+        let finished = finished.clone();
+
+        pool.execute(move || {
+            for _ in 0..JOBS / PARENTS {
+                inner.execute(tiny_job(&done, &finished));
+            }
+        });
+    }
+    wait.recv().unwrap();
+    let elapsed = start.elapsed();
+
+    // a parent may still hold its clone for a moment, the pool must not be dropped on a worker
+    while Arc::strong_count(&pool) > 1 {
+        thread::yield_now();
+    }
+
+    elapsed
+}
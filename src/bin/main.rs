@@ -1,47 +1,187 @@
-use multithread_server::ThreadPool;
-use std::fs;
-use std::io::prelude::*;
+use multithread_server::{
+    handle_connection, CancellationToken, Clock, Executor, InlineExecutor, Level, LogFormat,
+    Logger, Priority, Record, SystemClock, ThreadPerJobExecutor, ThreadPoolBuilder, Value,
+    WriterLogger,
+};
+use std::env;
 use std::net::{TcpListener, TcpStream};
-use std::thread;
+use std::process;
+use std::sync::Arc;
 use std::time::Duration;
 
+const ADDR: &str = "127.0.0.1:7878";
+// the watchdog warns about requests running longer than this (/sleep takes 5 seconds)
+const JOB_DEADLINE: Duration = Duration::from_secs(10);
+const USAGE: &str = "usage: main [--executor pool|inline|thread] [--log-format text|json] [--log-level error|warn|info|debug|trace] [--log-file PATH]";
+
+// how connections are run, --executor
+enum ExecutorKind {
+    Pool,
+    Inline,
+    ThreadPerJob,
+}
+
+struct Options {
+    executor: ExecutorKind,
+    logger: Arc<dyn Logger>,
+}
+
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    let pool = ThreadPool::new(5);
+    let Options { executor, logger } = match options_from_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let listener = TcpListener::bind(ADDR).unwrap();
+    log(&*logger, Level::Info, "listening", &[("addr", ADDR.into())]);
+
+    match executor {
+        ExecutorKind::Pool => {
+            // bounded queue -> the accept loop waits for a free slot instead of queueing
+            // connections until memory runs out
+            let pool = ThreadPoolBuilder::new()
+                .size(5)
+                .queue_capacity(100)
+                .thread_name("http")
+                .job_deadline(JOB_DEADLINE)
+                .logger(Arc::clone(&logger))
+                .build()
+                .unwrap();
+            serve(&listener, &pool, &logger);
+        }
+        ExecutorKind::Inline => serve(&listener, &InlineExecutor, &logger),
+        ExecutorKind::ThreadPerJob => serve(&listener, &ThreadPerJobExecutor, &logger),
+    }
+}
+
+// accept loop, every connection becomes one job on executor
+fn serve<E: Executor>(listener: &TcpListener, executor: &E, logger: &Arc<dyn Logger>) {
+    // /sleep waits on this
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock::new());
 
     for stream in listener.incoming() {
-        let stream = stream.unwrap();
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                let error = e.to_string();
+                log(
+                    &**logger,
+                    Level::Warn,
+                    "accept failed",
+                    &[("error", error.as_str().into())],
+                );
+                continue;
+            }
+        };
+        let priority = request_priority(&stream);
+        let logger = Arc::clone(logger);
+        let clock = Arc::clone(&clock);
+        // handle_connection cancels it once the client hung up
+        let token = CancellationToken::new();
+        let request_token = token.clone();
 
-        pool.execute(|| {
-            handle_connection(stream);
+        executor.execute_cancellable(priority, &token, move || {
+            if let Err(e) = handle_connection(stream, &request_token, &*clock, &*logger) {
+                let error = e.to_string();
+                log(
+                    &*logger,
+                    Level::Warn,
+                    "request failed",
+                    &[("error", error.as_str().into())],
+                );
+            }
         });
     }
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];
-    stream.read(&mut buffer).unwrap();
+// --executor pool|inline|thread (pool without it), --log-format text|json,
+// --log-level <level>, --log-file <path> (stderr without it)
+fn options_from_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut executor = ExecutorKind::Pool;
+    let mut format = LogFormat::Text;
+    let mut level = Level::Info;
+    let mut file = None;
 
-    let get = b"GET / HTTP/1.1\r\n";
-    let sleep = b"GET /sleep HTTP/1.1\r\n";
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
 
-    let (status_line, filename) = if buffer.starts_with(get) {
-        ("HTTP/1.1 200 OK", "hello.html")
-    } else if buffer.starts_with(sleep) {
-        thread::sleep(Duration::from_secs(5));
-        ("HTTP/1.1 200 OK", "hello.html")
-    } else {
-        ("HTTP/1.1 404 NOT FOUND", "404.html")
+        match arg.as_str() {
+            "--executor" => {
+                executor = match value()?.as_str() {
+                    "pool" => ExecutorKind::Pool,
+                    "inline" => ExecutorKind::Inline,
+                    "thread" => ExecutorKind::ThreadPerJob,
+                    other => return Err(format!("unknown executor {}", other)),
+                }
+            }
+            "--log-format" => {
+                format = match value()?.as_str() {
+                    "text" => LogFormat::Text,
+                    "json" => LogFormat::JsonLines,
+                    other => return Err(format!("unknown log format {}", other)),
+                }
+            }
+            "--log-level" => {
+                level = match value()?.as_str() {
+                    "error" => Level::Error,
+                    "warn" => Level::Warn,
+                    "info" => Level::Info,
+                    "debug" => Level::Debug,
+                    "trace" => Level::Trace,
+                    other => return Err(format!("unknown log level {}", other)),
+                }
+            }
+            "--log-file" => file = Some(value()?),
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+
+    let logger = match file {
+        Some(path) => WriterLogger::file(&path, format, level)
+            .map_err(|e| format!("can not open log file {}: {}", path, e))?,
+        None => WriterLogger::stderr(format, level),
     };
 
-    let contents = fs::read_to_string(filename).unwrap();
-    let response = format!(
-        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
-        status_line,
-        contents.len(),
-        contents
-    );
+    Ok(Options {
+        executor,
+        logger: Arc::new(logger),
+    })
+}
+
+fn log(logger: &dyn Logger, level: Level, message: &str, fields: &[(&str, Value<'_>)]) {
+    if logger.enabled(level) {
+        logger.log(&Record {
+            level,
+            target: "server",
+            message,
+            fields,
+        });
+    }
+}
+
+// health checks should not wait behind slow requests like /sleep
+// only looks at bytes that already arrived: the accept loop never waits on a client, one whose
+// request is not there yet gets Normal
+fn request_priority(stream: &TcpStream) -> Priority {
+    let mut buffer = [0; 32];
+
+    // peek leaves the bytes for handle_connection
+    let read = match stream.set_nonblocking(true) {
+        Ok(()) => stream.peek(&mut buffer).unwrap_or(0),
+        Err(_) => 0,
+    };
+    // if this fails the read in handle_connection fails too, a panic the pool catches
+    let _ = stream.set_nonblocking(false);
 
-    stream.write(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    let request = &buffer[..read];
+    if request.starts_with(b"GET /health ") {
+        Priority::High
+    } else if request.starts_with(b"GET /sleep ") {
+        Priority::Low
+    } else {
+        Priority::Normal
+    }
 }
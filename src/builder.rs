@@ -0,0 +1,165 @@
+use std::{any::Any, sync::Arc, thread, time::Duration};
+
+use crate::{
+    Logger, OverflowPolicy, PanicPolicy, PoolConfig, PoolCreationError, RateLimit, Scheduler,
+    ThreadPool,
+};
+
+// fluent way to fill in a PoolConfig
+// ThreadPoolBuilder::new().size(8).thread_name("http").stack_size(256 * 1024).build()
+#[derive(Clone)]
+pub struct ThreadPoolBuilder {
+    config: PoolConfig,
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+}
+
+impl ThreadPoolBuilder {
+    // one worker per CPU unless size says otherwise
+    pub fn new() -> ThreadPoolBuilder {
+        let size = thread::available_parallelism().map_or(1, |n| n.get());
+        ThreadPoolBuilder {
+            config: PoolConfig::new(size),
+        }
+    }
+
+    pub fn size(mut self, size: usize) -> ThreadPoolBuilder {
+        self.config.size = size;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> ThreadPoolBuilder {
+        self.config.max_size = Some(max_size);
+        self
+    }
+
+    pub fn keep_alive(mut self, keep_alive: Duration) -> ThreadPoolBuilder {
+        self.config.keep_alive = keep_alive;
+        self
+    }
+
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> ThreadPoolBuilder {
+        self.config.panic_policy = policy;
+        self
+    }
+
+    pub fn panic_hook<F>(mut self, hook: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize, &(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.config.panic_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn on_worker_start<F>(mut self, hook: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_worker_start = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn on_worker_stop<F>(mut self, hook: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_worker_stop = Some(Arc::new(hook));
+        self
+    }
+
+    // add a named queue (or set the weight of "default"), see PoolConfig::queues
+    pub fn queue(mut self, name: impl Into<String>, weight: u32) -> ThreadPoolBuilder {
+        self.config.queues.push((name.into(), weight));
+        self
+    }
+
+    // limit for the whole pool, see PoolConfig::rate_limit
+    pub fn rate_limit(mut self, limit: RateLimit) -> ThreadPoolBuilder {
+        self.config.rate_limit = Some(limit);
+        self
+    }
+
+    // limit for one named queue, see PoolConfig::queue_rate_limits
+    pub fn queue_rate_limit(
+        mut self,
+        queue: impl Into<String>,
+        limit: RateLimit,
+    ) -> ThreadPoolBuilder {
+        self.config.queue_rate_limits.push((queue.into(), limit));
+        self
+    }
+
+    pub fn queue_capacity(mut self, capacity: usize) -> ThreadPoolBuilder {
+        self.config.queue_capacity = Some(capacity);
+        self
+    }
+
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> ThreadPoolBuilder {
+        self.config.overflow_policy = policy;
+        self
+    }
+
+    pub fn starvation_limit(mut self, limit: Duration) -> ThreadPoolBuilder {
+        self.config.starvation_limit = limit;
+        self
+    }
+
+    pub fn scheduler(mut self, scheduler: Scheduler) -> ThreadPoolBuilder {
+        self.config.scheduler = scheduler;
+        self
+    }
+
+    // worker threads are named "<prefix>-<worker id>"
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> ThreadPoolBuilder {
+        self.config.thread_name = Some(prefix.into());
+        self
+    }
+
+    pub fn stack_size(mut self, bytes: usize) -> ThreadPoolBuilder {
+        self.config.stack_size = Some(bytes);
+        self
+    }
+
+    // Linux only: worker id is pinned to cpus[id % cpus.len()]
+    pub fn cpu_affinity(mut self, cpus: Vec<usize>) -> ThreadPoolBuilder {
+        self.config.cpu_affinity = Some(cpus);
+        self
+    }
+
+    // Linux only: nice value (-20 highest .. 19 lowest) for every worker thread
+    pub fn nice(mut self, nice: i32) -> ThreadPoolBuilder {
+        self.config.nice = Some(nice);
+        self
+    }
+
+    // see PoolConfig::job_deadline
+    pub fn job_deadline(mut self, deadline: Duration) -> ThreadPoolBuilder {
+        self.config.job_deadline = Some(deadline);
+        self
+    }
+
+    pub fn on_stuck_job<F>(mut self, hook: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize, Duration) + Send + Sync + 'static,
+    {
+        self.config.on_stuck_job = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn logger(mut self, logger: Arc<dyn Logger>) -> ThreadPoolBuilder {
+        self.config.logger = Some(logger);
+        self
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    pub fn build(self) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPool::with_config(self.config)
+    }
+}
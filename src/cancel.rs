@@ -0,0 +1,57 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// attach to a job with ThreadPool::execute_cancellable
+// clones share the flag: keep one to cancel, move another into the job to poll it
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    // a queued job is skipped, a running one only stops if it checks is_cancelled
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::CancellationToken;
+    use crate::{tests, Priority, ThreadPool};
+
+    #[test]
+    fn a_job_cancelled_while_queued_is_skipped_and_counted() {
+        let pool = ThreadPool::new(1);
+        let release = tests::block_worker(&pool);
+        let token = CancellationToken::new();
+        let ran = Arc::new(AtomicBool::new(false));
+
+        let job_ran = Arc::clone(&ran);
+        pool.execute_cancellable(Priority::Normal, &token, move || {
+            job_ran.store(true, Ordering::SeqCst);
+        });
+        token.cancel();
+        drop(release);
+        // one worker: the cancelled job left the queue before this one ran
+        pool.submit(|| {}).join().unwrap();
+
+        assert!(!ran.load(Ordering::SeqCst));
+        assert_eq!(pool.stats().cancelled, 1);
+    }
+}
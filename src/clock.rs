@@ -0,0 +1,76 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+// where code that waits gets its time from, so a test can swap real sleeping for a
+// VirtualClock that just jumps ahead
+pub trait Clock: Send + Sync {
+    // time since the clock started
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+// real time, sleep blocks the thread
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// time only moves when told to: sleep returns at once after moving the clock forward
+// clones share the same time, see Simulation::clock
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> VirtualClock {
+        VirtualClock::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    // never moves backwards, a time in the past is ignored
+    pub(crate) fn advance_to(&self, time: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = (*now).max(time);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
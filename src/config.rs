@@ -0,0 +1,287 @@
+use std::{any::Any, sync::Arc, time::Duration};
+
+use crate::{os, queue::DEFAULT_QUEUE_NAME, Logger, PoolCreationError, RateLimit, Scheduler};
+
+// called inside the worker thread with the worker id and the value passed to panic!
+pub type PanicHook = Arc<dyn Fn(usize, &(dyn Any + Send)) + Send + Sync>;
+
+// the worker id a PanicHook gets for a job that ran on the submitting thread
+// (OverflowPolicy::CallerRuns)
+pub const CALLER_WORKER: usize = usize::MAX;
+
+// called inside the worker thread with the worker id, see PoolConfig::on_worker_start
+pub type WorkerHook = Arc<dyn Fn(usize) + Send + Sync>;
+
+// called on the watchdog thread with the worker id and how long its job has been running
+pub type StuckJobHook = Arc<dyn Fn(usize, Duration) + Send + Sync>;
+
+// what a worker does after one of its jobs panicked
+// that includes the panics submit, groups, scopes, graphs and futures catch to hand them to the
+// caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    // swallow the panic and keep using the same thread
+    #[default]
+    KeepWorker,
+    // let the thread end and start a fresh one with the same worker id
+    Respawn,
+    // stop running jobs: queued and new jobs are dropped from now on
+    Poison,
+}
+
+// what a submission does when a bounded queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    // wait until a worker takes a job off the queue
+    #[default]
+    Block,
+    // refuse the job, try_execute returns ExecuteError::QueueFull
+    Reject,
+    // run the job right away on the thread that submitted it
+    CallerRuns,
+    // throw away the oldest job queued on the same named queue to make room
+    DropOldest,
+}
+
+// everything ThreadPool::with_config needs, fields are pub so callers can write
+// PoolConfig { panic_policy: PanicPolicy::Respawn, ..PoolConfig::new(4) }
+#[derive(Clone)]
+pub struct PoolConfig {
+    // workers started up front, an elastic pool never shrinks below it
+    pub size: usize,
+    // Some -> elastic pool: extra workers are spawned while jobs wait in the queue, up to max_size
+    pub max_size: Option<usize>,
+    // how long an extra worker may sit idle before it exits
+    pub keep_alive: Duration,
+    pub panic_policy: PanicPolicy,
+    pub panic_hook: Option<PanicHook>,
+    // run by every worker thread before its first job and after its last one, a respawned
+    // worker gets a new thread and runs both again -> the place for thread locals
+    pub on_worker_start: Option<WorkerHook>,
+    pub on_worker_stop: Option<WorkerHook>,
+    // (name, weight) of extra named queues for ThreadPool::execute_on, next to "default"
+    // (weight 1, use the name to change it); each of them gets queue_capacity slots of its own,
+    // overflow policy applies to each one separately
+    pub queues: Vec<(String, u32)>,
+    // Some -> jobs from all queues together are started at most this often
+    pub rate_limit: Option<RateLimit>,
+    // (queue name, limit) for the jobs of one named queue, "default" included
+    pub queue_rate_limits: Vec<(String, RateLimit)>,
+    // None -> unbounded queues, Some -> at most this many jobs in each named queue
+    pub queue_capacity: Option<usize>,
+    pub overflow_policy: OverflowPolicy,
+    // a queued Normal or Low job that waited this long runs before newer High jobs
+    pub starvation_limit: Duration,
+    pub scheduler: Scheduler,
+    // worker threads are named "<thread_name>-<worker id>", None -> unnamed
+    pub thread_name: Option<String>,
+    // None -> the std default (2 MiB unless RUST_MIN_STACK says otherwise)
+    pub stack_size: Option<usize>,
+    // Linux only: worker id is pinned to cpu_affinity[id % len]
+    pub cpu_affinity: Option<Vec<usize>>,
+    // Linux only: nice value of every worker thread
+    pub nice: Option<i32>,
+    // Some -> a watchdog thread reports jobs running longer than this (log, stats, hook)
+    // the job keeps running, nothing can stop a thread from the outside
+    pub job_deadline: Option<Duration>,
+    pub on_stuck_job: Option<StuckJobHook>,
+    // where the pool reports what it is doing, None -> nowhere
+    pub logger: Option<Arc<dyn Logger>>,
+}
+
+impl PoolConfig {
+    pub fn new(size: usize) -> PoolConfig {
+        PoolConfig {
+            size,
+            max_size: None,
+            keep_alive: Duration::from_secs(60),
+            panic_policy: PanicPolicy::default(),
+            panic_hook: None,
+            on_worker_start: None,
+            on_worker_stop: None,
+            queues: Vec::new(),
+            rate_limit: None,
+            queue_rate_limits: Vec::new(),
+            queue_capacity: None,
+            overflow_policy: OverflowPolicy::default(),
+            starvation_limit: Duration::from_secs(1),
+            scheduler: Scheduler::default(),
+            thread_name: Some("worker".to_string()),
+            stack_size: None,
+            cpu_affinity: None,
+            nice: None,
+            job_deadline: None,
+            on_stuck_job: None,
+            logger: None,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), PoolCreationError> {
+        // usize include 0, but create 0 thread has no sense
+        if self.size == 0 {
+            return Err(PoolCreationError::ZeroSize);
+        }
+
+        if let Some(max_size) = self.max_size {
+            if max_size < self.size {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "max_size {} is smaller than size {}",
+                    max_size, self.size
+                )));
+            }
+        }
+
+        for (i, (name, weight)) in self.queues.iter().enumerate() {
+            if *weight == 0 {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "queue {} needs a weight greater than zero",
+                    name
+                )));
+            }
+            if self.queues[..i].iter().any(|(earlier, _)| earlier == name) {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "queue {} is configured twice",
+                    name
+                )));
+            }
+        }
+
+        let limits = self
+            .rate_limit
+            .iter()
+            .map(|limit| (DEFAULT_QUEUE_NAME, limit));
+        let queue_limits = self
+            .queue_rate_limits
+            .iter()
+            .map(|(name, limit)| (name.as_str(), limit));
+        for (name, limit) in limits.chain(queue_limits) {
+            // NaN and infinity fail this too
+            let positive_rate = limit.per_second > 0.0 && limit.per_second.is_finite();
+            if !positive_rate || limit.burst == 0 {
+                return Err(invalid("rate limits need a positive rate and burst"));
+            }
+            let known = name == DEFAULT_QUEUE_NAME || self.queues.iter().any(|(q, _)| q == name);
+            if !known {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "rate limit for unknown queue {}",
+                    name
+                )));
+            }
+        }
+
+        if self.queue_capacity == Some(0) {
+            return Err(invalid("queue capacity must be greater than zero"));
+        }
+
+        // thread::Builder panics on a name with a nul byte
+        if self
+            .thread_name
+            .as_ref()
+            .is_some_and(|name| name.contains('\0'))
+        {
+            return Err(invalid("thread name must not contain a nul byte"));
+        }
+
+        if self.stack_size == Some(0) {
+            return Err(invalid("stack size must be greater than zero"));
+        }
+
+        if let Some(cpus) = &self.cpu_affinity {
+            if !cfg!(target_os = "linux") {
+                return Err(invalid("cpu affinity is only supported on Linux"));
+            }
+            if cpus.is_empty() {
+                return Err(invalid("cpu affinity needs at least one cpu"));
+            }
+            if let Some(cpu) = cpus.iter().find(|&&cpu| cpu >= os::MAX_CPUS) {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "cpu {} is out of range, the highest supported is {}",
+                    cpu,
+                    os::MAX_CPUS - 1
+                )));
+            }
+        }
+
+        if let Some(nice) = self.nice {
+            if !cfg!(target_os = "linux") {
+                return Err(invalid("thread nice values are only supported on Linux"));
+            }
+            if !(-20..=19).contains(&nice) {
+                return Err(PoolCreationError::InvalidConfig(format!(
+                    "nice value {} is outside -20..=19",
+                    nice
+                )));
+            }
+        }
+
+        if self.job_deadline == Some(Duration::ZERO) {
+            return Err(invalid("job deadline must be greater than zero"));
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(reason: &str) -> PoolCreationError {
+    PoolCreationError::InvalidConfig(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{PoolCreationError, RateLimit, ThreadPool, ThreadPoolBuilder};
+
+    #[test]
+    fn zero_size_is_rejected() {
+        assert!(matches!(
+            ThreadPool::build(0),
+            Err(PoolCreationError::ZeroSize)
+        ));
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let base = || ThreadPoolBuilder::new().size(2);
+        let mut zero_rate = RateLimit::per_second(1);
+        zero_rate.per_second = 0.0;
+        let mut zero_burst = RateLimit::per_second(1);
+        zero_burst.burst = 0;
+
+        let builders = [
+            ("max_size below size", base().max_size(1)),
+            ("zero weight", base().queue("batch", 0)),
+            ("queue twice", base().queue("batch", 1).queue("batch", 2)),
+            ("zero rate", base().rate_limit(zero_rate)),
+            ("zero burst", base().rate_limit(zero_burst)),
+            (
+                "unknown queue",
+                base().queue_rate_limit("missing", RateLimit::per_second(1)),
+            ),
+            ("zero capacity", base().queue_capacity(0)),
+            ("nul in thread name", base().thread_name("pool\0")),
+            ("zero stack size", base().stack_size(0)),
+            ("no cpus", base().cpu_affinity(Vec::new())),
+            ("nice out of range", base().nice(20)),
+            ("zero job deadline", base().job_deadline(Duration::ZERO)),
+        ];
+
+        for (case, builder) in builders {
+            assert!(
+                matches!(builder.build(), Err(PoolCreationError::InvalidConfig(_))),
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn cpu_outside_the_cpu_set_is_rejected() {
+        let result = ThreadPoolBuilder::new()
+            .size(1)
+            .cpu_affinity(vec![5000])
+            .build();
+
+        assert!(matches!(result, Err(PoolCreationError::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{error::Error, fmt, io};
+
+// returned by ThreadPool::build instead of panicking like ThreadPool::new does
+#[derive(Debug)]
+pub enum PoolCreationError {
+    // a pool without workers could never run a job
+    ZeroSize,
+    // the OS refused to start the thread for worker `id`
+    Spawn { id: usize, source: io::Error },
+    // the requested options can not be satisfied
+    InvalidConfig(String),
+    // the OS refused to start the watchdog thread (PoolConfig::job_deadline)
+    SpawnWatchdog(io::Error),
+}
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolCreationError::ZeroSize => write!(f, "thread pool size must be greater than zero"),
+            PoolCreationError::Spawn { id, source } => {
+                write!(f, "failed to spawn thread for worker {}: {}", id, source)
+            }
+            PoolCreationError::InvalidConfig(reason) => {
+                write!(f, "invalid thread pool configuration: {}", reason)
+            }
+            PoolCreationError::SpawnWatchdog(source) => {
+                write!(f, "failed to spawn watchdog thread: {}", source)
+            }
+        }
+    }
+}
+
+impl Error for PoolCreationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PoolCreationError::Spawn { source, .. } => Some(source),
+            PoolCreationError::SpawnWatchdog(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// returned by ThreadPool::try_execute when the job was not accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecuteError {
+    // the bounded queue is full and the pool uses OverflowPolicy::Reject
+    QueueFull,
+    // a job panicked under PanicPolicy::Poison, the pool no longer runs jobs
+    Poisoned,
+    // the pool is shutting down
+    ShutDown,
+    // execute_on got a queue name that is not in PoolConfig::queues
+    UnknownQueue,
+    // a rate limit with RateLimitPolicy::Reject is used up
+    RateLimited,
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteError::QueueFull => write!(f, "job queue is full"),
+            ExecuteError::Poisoned => write!(f, "thread pool is poisoned"),
+            ExecuteError::ShutDown => write!(f, "thread pool is shut down"),
+            ExecuteError::UnknownQueue => write!(f, "no queue with that name"),
+            ExecuteError::RateLimited => write!(f, "rate limit exceeded"),
+        }
+    }
+}
+
+impl Error for ExecuteError {}
@@ -0,0 +1,85 @@
+use std::thread;
+
+use crate::{CancellationToken, Priority, ThreadPool};
+
+// something that runs jobs, so code like the server's accept loop does not care whether it
+// gets a ThreadPool, a thread per job or everything inline
+// only execute is required, the others fall back to it
+pub trait Executor {
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static;
+
+    // executors without a queue have nothing to order, they ignore the priority
+    fn execute_with_priority<F>(&self, priority: Priority, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let _ = priority;
+        self.execute(f);
+    }
+
+    // f is skipped if token is cancelled before it starts
+    fn execute_cancellable<F>(&self, priority: Priority, token: &CancellationToken, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let token = token.clone();
+        self.execute_with_priority(priority, move || {
+            if !token.is_cancelled() {
+                f();
+            }
+        });
+    }
+}
+
+impl Executor for ThreadPool {
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        ThreadPool::execute(self, f);
+    }
+
+    fn execute_with_priority<F>(&self, priority: Priority, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        ThreadPool::execute_with_priority(self, priority, f);
+    }
+
+    fn execute_cancellable<F>(&self, priority: Priority, token: &CancellationToken, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        ThreadPool::execute_cancellable(self, priority, token, f);
+    }
+}
+
+// runs every job right away on the calling thread: one job at a time, in submission order
+// -> deterministic, meant for tests (a panicking job panics the caller)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineExecutor;
+
+impl Executor for InlineExecutor {
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        f();
+    }
+}
+
+// what the rust book server did before the pool: a new thread for every job
+// no limit on the number of threads, fine for comparisons, not for production
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadPerJobExecutor;
+
+impl Executor for ThreadPerJobExecutor {
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        thread::spawn(f);
+    }
+}
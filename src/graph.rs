@@ -0,0 +1,423 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+};
+
+use crate::{Priority, Shared, ThreadPool};
+
+// a node gets the results of the nodes it depends on, in the order the edges were added
+type NodeFn<T, E> = Box<dyn FnOnce(&[Arc<T>]) -> Result<T, E> + Send + 'static>;
+
+// build-style workloads: nodes are jobs, an edge a -> b means b needs a's result
+// let mut graph = TaskGraph::new();
+// let a = graph.add_node(|_| Ok(fetch("a")));
+// let b = graph.add_node(|_| Ok(fetch("b")));
+// let c = graph.add_node(|inputs| Ok(link(&inputs[0], &inputs[1])));
+// graph.add_edge(a, c);
+// graph.add_edge(b, c);
+// let results = graph.run(&pool)?;
+pub struct TaskGraph<T, E> {
+    tasks: Vec<NodeFn<T, E>>,
+    // per node: the nodes that need its result, and which input slot it goes to
+    dependents: Vec<Vec<(usize, usize)>>,
+    // per node: how many edges point at it
+    inputs: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+// how a node ended, see GraphResults::get
+#[derive(Debug)]
+pub enum NodeOutcome<T, E> {
+    Done(Arc<T>),
+    // the node returned Err
+    Failed(E),
+    // the node panicked, holds the value passed to panic!
+    Panicked(Box<dyn Any + Send + 'static>),
+    // the pool did not run the node (shut down, poisoned, queue full under Reject)
+    Cancelled,
+    // a node it depends on (directly or not) did not finish with Done
+    Skipped,
+}
+
+// returned by TaskGraph::run once every node has an outcome
+#[derive(Debug)]
+pub struct GraphResults<T, E> {
+    outcomes: Vec<NodeOutcome<T, E>>,
+}
+
+// TaskGraph::run found a cycle before running anything
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    // every node on a cycle or downstream of one, none of them could ever become ready
+    pub nodes: Vec<NodeId>,
+}
+
+impl NodeId {
+    // position in the order nodes were added, starting at 0
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl<T, E> Default for TaskGraph<T, E> {
+    fn default() -> TaskGraph<T, E> {
+        TaskGraph::new()
+    }
+}
+
+impl<T, E> TaskGraph<T, E> {
+    pub fn new() -> TaskGraph<T, E> {
+        TaskGraph {
+            tasks: Vec::new(),
+            dependents: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn add_node<F>(&mut self, f: F) -> NodeId
+    where
+        F: FnOnce(&[Arc<T>]) -> Result<T, E> + Send + 'static,
+    {
+        self.tasks.push(Box::new(f));
+        self.dependents.push(Vec::new());
+        self.inputs.push(0);
+        NodeId(self.tasks.len() - 1)
+    }
+
+    // `to` runs after `from` finished and gets its result as the next input
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
+        // like indexing a Vec out of bounds: a NodeId from another graph is a bug
+        assert!(
+            from.0 < self.tasks.len() && to.0 < self.tasks.len(),
+            "node does not belong to this graph"
+        );
+
+        self.dependents[from.0].push((to.0, self.inputs[to.0]));
+        self.inputs[to.0] += 1;
+    }
+
+    // Kahn's algorithm: whatever never gets to zero unfinished inputs sits on a cycle
+    fn check_cycles(&self) -> Result<(), CycleError> {
+        let mut waiting = self.inputs.clone();
+        let mut ready: VecDeque<usize> = (0..waiting.len()).filter(|&n| waiting[n] == 0).collect();
+
+        while let Some(node) = ready.pop_front() {
+            for &(dependent, _) in &self.dependents[node] {
+                waiting[dependent] -= 1;
+                if waiting[dependent] == 0 {
+                    ready.push_back(dependent);
+                }
+            }
+        }
+
+        let nodes: Vec<NodeId> = (0..waiting.len())
+            .filter(|&n| waiting[n] > 0)
+            .map(NodeId)
+            .collect();
+        match nodes.is_empty() {
+            true => Ok(()),
+            false => Err(CycleError { nodes }),
+        }
+    }
+}
+
+impl<T, E> TaskGraph<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    // run every node on the pool's workers, nodes whose inputs are ready run in parallel
+    // blocks the calling thread until every node is done, failed or skipped
+    // same caveat as ThreadPool::scope when called from a job on the same pool
+    pub fn run(self, pool: &ThreadPool) -> Result<GraphResults<T, E>, CycleError> {
+        self.check_cycles()?;
+
+        let count = self.tasks.len();
+        let run = Arc::new(GraphRun {
+            state: Mutex::new(GraphState {
+                waiting: self.inputs.clone(),
+                inputs: self.inputs.iter().map(|&n| vec![None; n]).collect(),
+                tasks: self.tasks.into_iter().map(Some).collect(),
+                outcomes: (0..count).map(|_| None).collect(),
+                unfinished: count,
+            }),
+            all_done: Condvar::new(),
+            dependents: self.dependents,
+            shared: Arc::clone(&pool.shared),
+        });
+
+        let ready: Vec<_> = {
+            let mut state = run.state.lock().unwrap();
+            (0..count)
+                .filter(|&node| self.inputs[node] == 0)
+                .map(|node| (node, state.tasks[node].take().unwrap(), Vec::new()))
+                .collect()
+        };
+        for (node, task, inputs) in ready {
+            run.start(node, task, inputs);
+        }
+
+        let mut state = run.state.lock().unwrap();
+        while state.unfinished > 0 {
+            state = run.all_done.wait(state).unwrap();
+        }
+
+        Ok(GraphResults {
+            outcomes: state.outcomes.drain(..).map(Option::unwrap).collect(),
+        })
+    }
+}
+
+impl<T, E> GraphResults<T, E> {
+    pub fn get(&self, node: NodeId) -> &NodeOutcome<T, E> {
+        &self.outcomes[node.0]
+    }
+
+    // Some only for NodeOutcome::Done
+    pub fn value(&self, node: NodeId) -> Option<&T> {
+        match self.get(node) {
+            NodeOutcome::Done(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|outcome| matches!(outcome, NodeOutcome::Done(_)))
+    }
+
+    // in NodeId order
+    pub fn into_outcomes(self) -> Vec<NodeOutcome<T, E>> {
+        self.outcomes
+    }
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nodes: Vec<usize> = self.nodes.iter().map(|node| node.0).collect();
+        write!(f, "task graph has a cycle, nodes {:?} can never run", nodes)
+    }
+}
+
+impl Error for CycleError {}
+
+// one TaskGraph::run in progress, shared by the caller and the node jobs
+struct GraphRun<T, E> {
+    state: Mutex<GraphState<T, E>>,
+    // signalled when the last node gets its outcome
+    all_done: Condvar,
+    dependents: Vec<Vec<(usize, usize)>>,
+    shared: Arc<Shared>,
+}
+
+struct GraphState<T, E> {
+    // per node: inputs that have not arrived yet
+    waiting: Vec<usize>,
+    inputs: Vec<Vec<Option<Arc<T>>>>,
+    // taken when the node starts or is skipped
+    tasks: Vec<Option<NodeFn<T, E>>>,
+    outcomes: Vec<Option<NodeOutcome<T, E>>>,
+    // nodes without an outcome
+    unfinished: usize,
+}
+
+impl<T, E> GraphState<T, E> {
+    fn set_outcome(&mut self, node: usize, outcome: NodeOutcome<T, E>) {
+        self.outcomes[node] = Some(outcome);
+        self.unfinished -= 1;
+    }
+
+    // node can not run anymore, neither can anything downstream of it
+    fn skip(&mut self, node: usize, dependents: &[Vec<(usize, usize)>]) {
+        if self.outcomes[node].is_some() {
+            return;
+        }
+        self.tasks[node] = None;
+        self.set_outcome(node, NodeOutcome::Skipped);
+
+        for &(dependent, _) in &dependents[node] {
+            self.skip(dependent, dependents);
+        }
+    }
+}
+
+impl<T, E> GraphRun<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    fn start(self: &Arc<Self>, node: usize, task: NodeFn<T, E>, inputs: Vec<Arc<T>>) {
+        let job = NodeJob {
+            task: Some(task),
+            inputs,
+            node,
+            run: Arc::clone(self),
+        };
+
+        // a node the pool does not accept is dropped here and ends up Cancelled
+        let _ = self
+            .shared
+            .push_job(Priority::Normal, Box::new(move || job.run()), None);
+    }
+
+    fn finish(self: &Arc<Self>, node: usize, outcome: NodeOutcome<T, E>) {
+        let mut ready = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+
+            match &outcome {
+                NodeOutcome::Done(value) => {
+                    for &(dependent, slot) in &self.dependents[node] {
+                        state.inputs[dependent][slot] = Some(Arc::clone(value));
+                        state.waiting[dependent] -= 1;
+
+                        // a skipped dependent stays skipped even once all its inputs are in
+                        if state.waiting[dependent] == 0 && state.outcomes[dependent].is_none() {
+                            let task = state.tasks[dependent].take().unwrap();
+                            let inputs = state.inputs[dependent]
+                                .drain(..)
+                                .map(Option::unwrap)
+                                .collect();
+                            ready.push((dependent, task, inputs));
+                        }
+                    }
+                }
+                _ => {
+                    for &(dependent, _) in &self.dependents[node] {
+                        state.skip(dependent, &self.dependents);
+                    }
+                }
+            }
+
+            state.set_outcome(node, outcome);
+            if state.unfinished == 0 {
+                self.all_done.notify_all();
+            }
+        }
+
+        // outside the lock: pushing may block on a full queue or run the node right here
+        for (node, task, inputs) in ready {
+            self.start(node, task, inputs);
+        }
+    }
+}
+
+// dropping it without running reports the node as Cancelled, so run never waits for a node
+// the pool threw away
+struct NodeJob<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    task: Option<NodeFn<T, E>>,
+    inputs: Vec<Arc<T>>,
+    node: usize,
+    run: Arc<GraphRun<T, E>>,
+}
+
+impl<T, E> NodeJob<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    fn run(mut self) {
+        let task = self.task.take().unwrap();
+        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| task(&self.inputs))) {
+            Ok(Ok(value)) => NodeOutcome::Done(Arc::new(value)),
+            Ok(Err(error)) => NodeOutcome::Failed(error),
+            Err(payload) => {
+                self.run.shared.caught_panic(payload.as_ref());
+                NodeOutcome::Panicked(payload)
+            }
+        };
+        self.run.finish(self.node, outcome);
+    }
+}
+
+impl<T, E> Drop for NodeJob<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+{
+    fn drop(&mut self) {
+        if self.task.take().is_some() {
+            self.run.finish(self.node, NodeOutcome::Cancelled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NodeOutcome, TaskGraph};
+    use crate::ThreadPool;
+
+    #[test]
+    fn inputs_arrive_in_edge_order() {
+        let pool = ThreadPool::new(2);
+        let mut graph: TaskGraph<String, ()> = TaskGraph::new();
+        let a = graph.add_node(|_| Ok("a".to_string()));
+        let b = graph.add_node(|_| Ok("b".to_string()));
+        let joined = graph.add_node(|inputs| {
+            Ok(inputs
+                .iter()
+                .map(|input| input.as_str())
+                .collect::<String>())
+        });
+        graph.add_edge(b, joined);
+        graph.add_edge(a, joined);
+
+        let results = graph.run(&pool).unwrap();
+
+        assert!(results.is_success());
+        assert_eq!(results.value(joined).map(String::as_str), Some("ba"));
+    }
+
+    #[test]
+    fn a_failure_skips_everything_downstream() {
+        let pool = ThreadPool::new(2);
+        let mut graph: TaskGraph<u32, &str> = TaskGraph::new();
+        let failed = graph.add_node(|_| Err("nope"));
+        let child = graph.add_node(|_| Ok(1));
+        let grandchild = graph.add_node(|_| Ok(2));
+        let panicked = graph.add_node(|_| panic!("boom"));
+        let independent = graph.add_node(|_| Ok(3));
+        graph.add_edge(failed, child);
+        graph.add_edge(child, grandchild);
+        graph.add_edge(panicked, grandchild);
+
+        let results = graph.run(&pool).unwrap();
+
+        assert!(!results.is_success());
+        assert!(matches!(results.get(failed), NodeOutcome::Failed("nope")));
+        assert!(matches!(results.get(child), NodeOutcome::Skipped));
+        assert!(matches!(results.get(grandchild), NodeOutcome::Skipped));
+        assert!(matches!(results.get(panicked), NodeOutcome::Panicked(_)));
+        assert_eq!(results.value(independent), Some(&3));
+    }
+
+    #[test]
+    fn a_cycle_is_reported_before_anything_runs() {
+        let pool = ThreadPool::new(2);
+        let mut graph: TaskGraph<u32, ()> = TaskGraph::new();
+        let start = graph.add_node(|_| panic!("must not run"));
+        let a = graph.add_node(|_| Ok(1));
+        let b = graph.add_node(|_| Ok(2));
+        let downstream = graph.add_node(|_| Ok(3));
+        graph.add_edge(start, a);
+        graph.add_edge(a, b);
+        graph.add_edge(b, a);
+        graph.add_edge(b, downstream);
+
+        let error = graph.run(&pool).unwrap_err();
+
+        assert_eq!(error.nodes, vec![a, b, downstream]);
+        assert_eq!(pool.stats().completed, 0);
+    }
+}
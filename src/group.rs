@@ -0,0 +1,259 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{JoinError, Priority, Shared, ThreadPool};
+
+// a set of related jobs the caller can wait for as a whole
+// let group = JobGroup::new(&pool);
+// for cache in caches { group.spawn(move || cache.warm()) }
+// let summary = group.wait();
+pub struct JobGroup {
+    shared: Arc<Shared>,
+    state: Arc<GroupState>,
+    // jobs spawned so far, also the index of the next one in GroupFailure::job
+    spawned: usize,
+}
+
+// returned by JobGroup::wait once every job of the group is done
+#[derive(Debug)]
+pub struct GroupSummary {
+    // jobs that returned normally
+    pub completed: usize,
+    // in the order they happened, not the order the jobs were spawned
+    pub failures: Vec<GroupFailure>,
+}
+
+#[derive(Debug)]
+pub struct GroupFailure {
+    // 0 for the first job spawned on the group, 1 for the second, ...
+    pub job: usize,
+    pub error: JoinError,
+}
+
+struct GroupState {
+    progress: Mutex<Progress>,
+    // signalled when the last pending job finishes
+    all_done: Condvar,
+}
+
+struct Progress {
+    pending: usize,
+    completed: usize,
+    failures: Vec<GroupFailure>,
+}
+
+impl GroupState {
+    fn finish(&self, job: usize, result: Result<(), JoinError>) {
+        let mut progress = self.progress.lock().unwrap();
+        match result {
+            Ok(()) => progress.completed += 1,
+            Err(error) => progress.failures.push(GroupFailure { job, error }),
+        }
+
+        progress.pending -= 1;
+        if progress.pending == 0 {
+            self.all_done.notify_all();
+        }
+    }
+}
+
+// dropping it without running counts the job as cancelled, so wait never hangs on a job the
+// pool threw away (rejected, poisoned pool, discarded at shutdown)
+struct GroupJob<F> {
+    f: Option<F>,
+    job: usize,
+    state: Arc<GroupState>,
+}
+
+impl<F: FnOnce()> GroupJob<F> {
+    fn run(mut self, shared: &Shared) {
+        let f = self.f.take().unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+            shared.caught_panic(payload.as_ref());
+            JoinError::Panicked(payload)
+        });
+        self.state.finish(self.job, result);
+    }
+}
+
+impl<F> Drop for GroupJob<F> {
+    fn drop(&mut self) {
+        if self.f.take().is_some() {
+            self.state.finish(self.job, Err(JoinError::Cancelled));
+        }
+    }
+}
+
+impl JobGroup {
+    pub fn new(pool: &ThreadPool) -> JobGroup {
+        JobGroup {
+            shared: Arc::clone(&pool.shared),
+            state: Arc::new(GroupState {
+                progress: Mutex::new(Progress {
+                    pending: 0,
+                    completed: 0,
+                    failures: Vec::new(),
+                }),
+                all_done: Condvar::new(),
+            }),
+            spawned: 0,
+        }
+    }
+
+    // a panic in f ends up in the summary, the pool's panic hook and policy still see it
+    pub fn spawn<F>(&mut self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.state.progress.lock().unwrap().pending += 1;
+
+        let job = GroupJob {
+            f: Some(f),
+            job: self.spawned,
+            state: Arc::clone(&self.state),
+        };
+        self.spawned += 1;
+        let shared = Arc::clone(&self.shared);
+
+        // a job the pool did not accept is dropped right here and shows up as cancelled
+        let _ = self
+            .shared
+            .push_job(Priority::Normal, Box::new(move || job.run(&shared)), None);
+    }
+
+    // block the calling thread until every job spawned so far has finished
+    // the workers keep running jobs meanwhile, none of them waits on the group
+    pub fn wait(self) -> GroupSummary {
+        let mut progress = self.state.progress.lock().unwrap();
+        while progress.pending > 0 {
+            progress = self.state.all_done.wait(progress).unwrap();
+        }
+
+        GroupSummary {
+            completed: progress.completed,
+            failures: progress.failures.drain(..).collect(),
+        }
+    }
+
+    // false -> some jobs are still running after timeout, wait again or give up on them
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut progress = self.state.progress.lock().unwrap();
+
+        while progress.pending > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            progress = self
+                .state
+                .all_done
+                .wait_timeout(progress, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        true
+    }
+}
+
+impl GroupSummary {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        time::Duration,
+    };
+
+    use super::JobGroup;
+    use crate::{tests, JoinError, OverflowPolicy, ThreadPool, ThreadPoolBuilder};
+
+    #[test]
+    fn wait_returns_once_every_job_finished() {
+        let pool = ThreadPool::new(2);
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let mut group = JobGroup::new(&pool);
+        for _ in 0..5 {
+            let finished = Arc::clone(&finished);
+            group.spawn(move || {
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        let summary = group.wait();
+
+        assert_eq!(finished.load(Ordering::SeqCst), 5);
+        assert_eq!(summary.completed, 5);
+        assert!(summary.is_success());
+    }
+
+    #[test]
+    fn wait_timeout_gives_up_on_a_running_job() {
+        let pool = ThreadPool::new(1);
+        let (release, blocked) = mpsc::channel::<()>();
+
+        let mut group = JobGroup::new(&pool);
+        group.spawn(move || {
+            let _ = blocked.recv();
+        });
+
+        assert!(!group.wait_timeout(Duration::from_millis(20)));
+        release.send(()).unwrap();
+        assert!(group.wait_timeout(Duration::from_secs(5)));
+        assert_eq!(group.wait().completed, 1);
+    }
+
+    #[test]
+    fn failures_name_the_job_that_panicked() {
+        let pool = ThreadPool::new(2);
+
+        let mut group = JobGroup::new(&pool);
+        group.spawn(|| {});
+        group.spawn(|| panic!("first"));
+        group.spawn(|| {});
+        group.spawn(|| panic!("second"));
+        let summary = group.wait();
+
+        let mut failed: Vec<usize> = summary.failures.iter().map(|f| f.job).collect();
+        failed.sort();
+        assert_eq!(failed, [1, 3]);
+        assert!(summary
+            .failures
+            .iter()
+            .all(|f| matches!(f.error, JoinError::Panicked(_))));
+        assert_eq!(summary.completed, 2);
+    }
+
+    #[test]
+    fn a_job_refused_by_a_full_queue_is_cancelled() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue_capacity(1)
+            .overflow_policy(OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+        let release = tests::block_worker(&pool);
+        pool.execute(|| {});
+
+        let mut group = JobGroup::new(&pool);
+        group.spawn(|| {});
+        drop(release);
+        let summary = group.wait();
+
+        assert_eq!(summary.completed, 0);
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].job, 0);
+        assert!(matches!(summary.failures[0].error, JoinError::Cancelled));
+    }
+}
@@ -0,0 +1,122 @@
+use std::{any::Any, cell::Cell, error::Error, fmt, sync::mpsc, time::Duration};
+
+// returned by ThreadPool::submit, the job sends its result back through the channel
+pub struct JobHandle<T> {
+    receiver: mpsc::Receiver<Result<T, JoinError>>,
+    // set once try_join / join_timeout handed out the result, the channel is empty after that
+    joined: Cell<bool>,
+}
+
+#[derive(Debug)]
+pub enum JoinError {
+    // the job panicked, holds the value passed to panic!
+    Panicked(Box<dyn Any + Send + 'static>),
+    // the job was dropped without running (e.g. the pool went away first)
+    Cancelled,
+    // an earlier try_join / join_timeout already returned the result
+    AlreadyJoined,
+}
+
+impl<T> JobHandle<T> {
+    pub(crate) fn new(receiver: mpsc::Receiver<Result<T, JoinError>>) -> JobHandle<T> {
+        JobHandle {
+            receiver,
+            joined: Cell::new(false),
+        }
+    }
+
+    // block until the job has finished
+    pub fn join(self) -> Result<T, JoinError> {
+        if self.joined.get() {
+            return Err(JoinError::AlreadyJoined);
+        }
+        // the sender only disappears without a message if the job never ran
+        self.receiver.recv().unwrap_or(Err(JoinError::Cancelled))
+    }
+
+    // None -> the job has not finished yet
+    pub fn try_join(&self) -> Option<Result<T, JoinError>> {
+        if self.joined.get() {
+            return Some(Err(JoinError::AlreadyJoined));
+        }
+
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(JoinError::Cancelled),
+        };
+        self.joined.set(true);
+        Some(result)
+    }
+
+    // None -> the job did not finish within the timeout
+    pub fn join_timeout(&self, timeout: Duration) -> Option<Result<T, JoinError>> {
+        if self.joined.get() {
+            return Some(Err(JoinError::AlreadyJoined));
+        }
+
+        let result = match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => return None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(JoinError::Cancelled),
+        };
+        self.joined.set(true);
+        Some(result)
+    }
+}
+
+impl JoinError {
+    // panic! with a literal or a format string gives a &str or String payload
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            JoinError::Panicked(payload) => payload_message(payload.as_ref()),
+            JoinError::Cancelled | JoinError::AlreadyJoined => None,
+        }
+    }
+}
+
+pub(crate) fn payload_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        Some(s)
+    } else {
+        payload.downcast_ref::<String>().map(|s| s.as_str())
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Panicked(_) => match self.panic_message() {
+                Some(msg) => write!(f, "job panicked: {}", msg),
+                None => write!(f, "job panicked"),
+            },
+            JoinError::Cancelled => write!(f, "job was cancelled before it finished"),
+            JoinError::AlreadyJoined => write!(f, "job result was already taken"),
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{JoinError, ThreadPool};
+
+    #[test]
+    fn result_is_handed_out_once() {
+        let pool = ThreadPool::new(1);
+        let handle = pool.submit(|| 5);
+
+        assert!(matches!(
+            handle.join_timeout(Duration::from_secs(5)),
+            Some(Ok(5))
+        ));
+        assert!(matches!(
+            handle.try_join(),
+            Some(Err(JoinError::AlreadyJoined))
+        ));
+        assert!(matches!(handle.join(), Err(JoinError::AlreadyJoined)));
+    }
+}
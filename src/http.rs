@@ -0,0 +1,465 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use crate::{CancellationToken, Clock, Level, Logger, Record, ThreadPool, Value};
+
+// a request that sat in the queue longer than this gets a 504, the client has probably given up
+const QUEUE_DEADLINE: Duration = Duration::from_secs(3);
+// /sleep takes SLEEP_SLICES * SLEEP_SLICE = 5 seconds, checking for a hung up client in between
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+const SLEEP_SLICES: u32 = 50;
+
+// what handle_connection reads the request from and writes the response to: a TcpStream in the
+// server, something in memory in tests
+pub trait Connection: Read + Write {
+    // the client closed its end, nobody is going to read the response
+    fn peer_hung_up(&self) -> bool;
+}
+
+impl Connection for TcpStream {
+    // the request was read already, so a closed connection is the only way peek sees 0 bytes
+    // can not tell -> false, the write at the end finds out anyway
+    fn peer_hung_up(&self) -> bool {
+        let mut byte = [0; 1];
+
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let hung_up = match self.peek(&mut byte) {
+            Ok(read) => read == 0,
+            Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+        };
+        let _ = self.set_nonblocking(false);
+
+        hung_up
+    }
+}
+
+// serve the one request on connection: / and /health answer right away, /sleep after 5 seconds
+// on clock (a Simulation's VirtualClock in tests), anything else is a 404
+// token is the request's: cancelled here once the client hung up, and /sleep stops early when
+// someone else cancels it
+// Err -> reading the request or writing the response failed
+pub fn handle_connection<C: Connection>(
+    connection: C,
+    token: &CancellationToken,
+    clock: &dyn Clock,
+    logger: &dyn Logger,
+) -> io::Result<()> {
+    let waited = ThreadPool::current_queue_wait().unwrap_or_default();
+    respond(connection, waited, token, clock, logger)
+}
+
+// handle_connection for a request that waited in the queue for waited
+fn respond<C: Connection>(
+    mut connection: C,
+    waited: Duration,
+    token: &CancellationToken,
+    clock: &dyn Clock,
+    logger: &dyn Logger,
+) -> io::Result<()> {
+    let mut buffer = [0; 1024];
+    let read = connection.read(&mut buffer)?;
+
+    // first line of the request, e.g. "GET /sleep HTTP/1.1"
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let request_line = request.lines().next().unwrap_or("");
+
+    // the client gave up while the request sat in the queue, nobody reads the answer
+    if connection.peer_hung_up() {
+        token.cancel();
+        log(
+            logger,
+            Level::Info,
+            "request cancelled",
+            &[("request", request_line.into())],
+        );
+        return Ok(());
+    }
+
+    if waited > QUEUE_DEADLINE {
+        let status_line = "HTTP/1.1 504 GATEWAY TIMEOUT";
+        let response = format!("{}\r\nContent-Length: 0\r\n\r\n", status_line);
+        // the client may be gone already, nothing to do about it then
+        let _ = connection.write_all(response.as_bytes());
+        log(
+            logger,
+            Level::Warn,
+            "request waited too long",
+            &[
+                ("request", request_line.into()),
+                ("status", status_line.into()),
+                ("waited_ms", (waited.as_millis() as u64).into()),
+            ],
+        );
+        return Ok(());
+    }
+
+    let get = b"GET / HTTP/1.1\r\n";
+    let sleep = b"GET /sleep HTTP/1.1\r\n";
+    let health = b"GET /health HTTP/1.1\r\n";
+
+    let (status_line, filename) = if buffer.starts_with(get) || buffer.starts_with(health) {
+        ("HTTP/1.1 200 OK", "hello.html")
+    } else if buffer.starts_with(sleep) {
+        // sleep in slices so a client that gave up does not keep the worker for 5 seconds
+        for _ in 0..SLEEP_SLICES {
+            if connection.peer_hung_up() {
+                token.cancel();
+            }
+            if token.is_cancelled() {
+                log(
+                    logger,
+                    Level::Info,
+                    "request cancelled",
+                    &[("request", request_line.into())],
+                );
+                return Ok(());
+            }
+            clock.sleep(SLEEP_SLICE);
+        }
+        ("HTTP/1.1 200 OK", "hello.html")
+    } else {
+        ("HTTP/1.1 404 NOT FOUND", "404.html")
+    };
+
+    let contents = fs::read_to_string(filename)?;
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+        status_line,
+        contents.len(),
+        contents
+    );
+
+    connection.write_all(response.as_bytes())?;
+    connection.flush()?;
+
+    log(
+        logger,
+        Level::Info,
+        "request",
+        &[
+            ("request", request_line.into()),
+            ("status", status_line.into()),
+        ],
+    );
+    Ok(())
+}
+
+fn log(logger: &dyn Logger, level: Level, message: &str, fields: &[(&str, Value<'_>)]) {
+    if logger.enabled(level) {
+        logger.log(&Record {
+            level,
+            target: "server",
+            message,
+            fields,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Cursor, Read, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{handle_connection, respond, Connection, QUEUE_DEADLINE};
+    use crate::{
+        CancellationToken, Clock, Executor, Level, Logger, Priority, Record, Simulation,
+        VirtualClock,
+    };
+
+    // a client that sends `request` and, if hang_up_at is set, gives up at that virtual time
+    struct TestConnection {
+        request: Cursor<Vec<u8>>,
+        response: Arc<Mutex<Vec<u8>>>,
+        clock: VirtualClock,
+        hang_up_at: Option<Duration>,
+    }
+
+    impl Read for TestConnection {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.request.read(buf)
+        }
+    }
+
+    impl Write for TestConnection {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.response.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for TestConnection {
+        fn peer_hung_up(&self) -> bool {
+            self.hang_up_at.is_some_and(|at| self.clock.now() >= at)
+        }
+    }
+
+    // (virtual time, message) of every record
+    struct TestLogger {
+        records: Mutex<Vec<(Duration, String)>>,
+        clock: VirtualClock,
+    }
+
+    impl Logger for TestLogger {
+        fn enabled(&self, _level: Level) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            let request = record.fields.iter().find(|(name, _)| *name == "request");
+            let message = match request {
+                Some((_, request)) => format!("{}: {}", record.message, request),
+                None => record.message.to_string(),
+            };
+            self.records
+                .lock()
+                .unwrap()
+                .push((self.clock.now(), message));
+        }
+    }
+
+    // a one worker server on sim: every request is a job that runs on the sim's clock
+    struct TestServer {
+        sim: Simulation,
+        logger: Arc<TestLogger>,
+    }
+
+    impl TestServer {
+        fn new(seed: u64) -> TestServer {
+            let sim = Simulation::new(seed);
+            let logger = Arc::new(TestLogger {
+                records: Mutex::new(Vec::new()),
+                clock: sim.clock(),
+            });
+            TestServer { sim, logger }
+        }
+
+        // the job for one request, the response ends up in the returned buffer
+        fn request(
+            &self,
+            path: &str,
+            hang_up_at: Option<Duration>,
+        ) -> (impl FnOnce() + Send + 'static, Arc<Mutex<Vec<u8>>>) {
+            self.cancellable_request(path, hang_up_at, CancellationToken::new())
+        }
+
+        fn cancellable_request(
+            &self,
+            path: &str,
+            hang_up_at: Option<Duration>,
+            token: CancellationToken,
+        ) -> (impl FnOnce() + Send + 'static, Arc<Mutex<Vec<u8>>>) {
+            let (connection, response) = self.connection(path, hang_up_at);
+            let clock = self.sim.clock();
+            let logger = Arc::clone(&self.logger);
+
+            let job = move || handle_connection(connection, &token, &clock, &*logger).unwrap();
+            (job, response)
+        }
+
+        // a client asking for path on the sim's clock, the response ends up in the buffer
+        fn connection(
+            &self,
+            path: &str,
+            hang_up_at: Option<Duration>,
+        ) -> (TestConnection, Arc<Mutex<Vec<u8>>>) {
+            let response = Arc::new(Mutex::new(Vec::new()));
+            let connection = TestConnection {
+                request: Cursor::new(format!("GET {} HTTP/1.1\r\n\r\n", path).into_bytes()),
+                response: Arc::clone(&response),
+                clock: self.sim.clock(),
+                hang_up_at,
+            };
+            (connection, response)
+        }
+
+        fn records(&self) -> Vec<(Duration, String)> {
+            self.logger.records.lock().unwrap().clone()
+        }
+    }
+
+    fn text(response: &Mutex<Vec<u8>>) -> String {
+        String::from_utf8(response.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn sleep_answers_after_five_virtual_seconds() {
+        let server = TestServer::new(1);
+        let (job, response) = server.request("/sleep", None);
+        server.sim.execute(job);
+
+        server.sim.advance(Duration::from_secs(1));
+
+        assert!(text(&response).contains("200 OK"));
+        assert_eq!(server.sim.now(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn a_request_waits_behind_sleep() {
+        let server = TestServer::new(1);
+        let (sleep, _) = server.request("/sleep", None);
+        let (health, _) = server.request("/health", None);
+        server.sim.execute_after(Duration::from_secs(1), sleep);
+        server.sim.execute_after(Duration::from_secs(2), health);
+
+        server.sim.advance(Duration::from_secs(10));
+
+        assert_eq!(
+            server.records(),
+            [
+                (
+                    Duration::from_secs(6),
+                    "request: GET /sleep HTTP/1.1".to_string()
+                ),
+                (
+                    Duration::from_secs(6),
+                    "request: GET /health HTTP/1.1".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sleep_stops_once_the_client_hangs_up() {
+        let server = TestServer::new(1);
+        let token = CancellationToken::new();
+        let (job, response) =
+            server.cancellable_request("/sleep", Some(Duration::from_secs(1)), token.clone());
+        server.sim.execute(job);
+
+        server.sim.run_until_idle();
+
+        assert!(token.is_cancelled());
+        assert!(text(&response).is_empty());
+        assert_eq!(
+            server.records(),
+            [(
+                Duration::from_secs(1),
+                "request cancelled: GET /sleep HTTP/1.1".to_string()
+            )]
+        );
+    }
+
+    // stands in for whoever cancels the request while it sleeps: cancels token once the clock
+    // reaches at
+    struct CancellingClock {
+        clock: VirtualClock,
+        at: Duration,
+        token: CancellationToken,
+    }
+
+    impl Clock for CancellingClock {
+        fn now(&self) -> Duration {
+            self.clock.now()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.clock.sleep(duration);
+            if self.now() >= self.at {
+                self.token.cancel();
+            }
+        }
+    }
+
+    #[test]
+    fn sleep_stops_once_its_token_is_cancelled() {
+        let server = TestServer::new(1);
+        let token = CancellationToken::new();
+        let (connection, response) = server.connection("/sleep", None);
+        let clock = CancellingClock {
+            clock: server.sim.clock(),
+            at: Duration::from_secs(2),
+            token: token.clone(),
+        };
+
+        handle_connection(connection, &token, &clock, &*server.logger).unwrap();
+
+        assert!(text(&response).is_empty());
+        assert_eq!(
+            server.records(),
+            [(
+                Duration::from_secs(2),
+                "request cancelled: GET /sleep HTTP/1.1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn a_request_that_waited_too_long_gets_a_504() {
+        let server = TestServer::new(1);
+        let (connection, response) = server.connection("/sleep", None);
+        let waited = QUEUE_DEADLINE + Duration::from_millis(1);
+
+        let clock = server.sim.clock();
+        respond(
+            connection,
+            waited,
+            &CancellationToken::new(),
+            &clock,
+            &*server.logger,
+        )
+        .unwrap();
+
+        assert!(text(&response).starts_with("HTTP/1.1 504 GATEWAY TIMEOUT\r\n"));
+        // answered right away, without the 5 seconds of /sleep
+        assert_eq!(server.sim.now(), Duration::ZERO);
+        assert_eq!(
+            server.records(),
+            [(
+                Duration::ZERO,
+                "request waited too long: GET /sleep HTTP/1.1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn a_cancelled_request_never_starts() {
+        let server = TestServer::new(1);
+        let (sleep, _) = server.request("/sleep", None);
+        let token = CancellationToken::new();
+        let (health, response) = server.cancellable_request("/health", None, token.clone());
+        server.sim.execute(sleep);
+        server
+            .sim
+            .execute_cancellable(Priority::Normal, &token, health);
+
+        token.cancel();
+        server.sim.run_until_idle();
+
+        assert!(text(&response).is_empty());
+        assert_eq!(server.records().len(), 1);
+    }
+
+    // every request at once: the seed alone decides the order they are served in
+    fn served_order(seed: u64) -> Vec<(Duration, String)> {
+        let server = TestServer::new(seed);
+        for path in ["/", "/health", "/sleep", "/missing", "/sleep"] {
+            let (job, _) = server.request(path, None);
+            server.sim.execute(job);
+        }
+        server.sim.run_until_idle();
+        server.records()
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_order() {
+        let orders: Vec<_> = (0..8).map(served_order).collect();
+
+        for (seed, order) in orders.iter().enumerate() {
+            assert_eq!(*order, served_order(seed as u64), "seed {}", seed);
+        }
+        assert!(orders.iter().any(|order| *order != orders[0]));
+    }
+}
@@ -1,42 +1,241 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    any::Any,
+    cell::Cell,
+    future::Future,
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex, OnceLock,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
+mod builder;
+mod cancel;
+mod clock;
+mod config;
+mod error;
+mod executor;
+mod graph;
+mod group;
+mod handle;
+mod http;
+mod logging;
+mod metrics;
+mod os;
+mod parallel;
+mod queue;
+mod rate;
+mod scheduler;
+mod scope;
+mod shutdown;
+mod sim;
+mod task;
+mod timer;
+mod watchdog;
+
+pub use builder::ThreadPoolBuilder;
+pub use cancel::CancellationToken;
+pub use clock::{Clock, SystemClock, VirtualClock};
+pub use config::{
+    OverflowPolicy, PanicHook, PanicPolicy, PoolConfig, StuckJobHook, WorkerHook, CALLER_WORKER,
+};
+pub use error::{ExecuteError, PoolCreationError};
+pub use executor::{Executor, InlineExecutor, ThreadPerJobExecutor};
+pub use graph::{CycleError, GraphResults, NodeId, NodeOutcome, TaskGraph};
+pub use group::{GroupFailure, GroupSummary, JobGroup};
+pub use handle::{JobHandle, JoinError};
+pub use http::{handle_connection, Connection};
+pub use logging::{Level, LogFormat, Logger, Record, Value, WriterLogger};
+pub use metrics::{Histogram, PoolStats};
+pub use queue::{OverflowStats, Priority, QueueStats};
+pub use rate::{RateLimit, RateLimitPolicy, RateLimitStats};
+pub use scheduler::Scheduler;
+pub use scope::Scope;
+pub use shutdown::{ShutdownMode, ShutdownReport};
+pub use sim::Simulation;
+pub use task::block_on;
+pub use timer::TimerHandle;
+
+use metrics::Metrics;
+use os::ThreadOptions;
+use queue::{JobQueue, Pop, DEFAULT_QUEUE};
+use scheduler::{LocalQueue, Stealing};
+use timer::Timer;
+use watchdog::{JobWatch, Watchdog};
+
 pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+    // started by the first execute_after / execute_every
+    timer: OnceLock<Timer>,
+    // Some -> PoolConfig::job_deadline is set
+    watchdog: Option<Watchdog>,
+}
+
+// state every worker thread (and the timer thread) needs, shared through an Arc
+struct Shared {
+    // Mutex because an elastic pool adds workers from execute, which only has &self
+    workers: Mutex<Vec<Worker>>,
+    // multiple producers, multiple consumers
+    // -> Arc (around Shared): let multiple workers own the queue
+    // -> the queue locks internally so only one worker takes a given job
+    queue: JobQueue,
+    // Some -> Scheduler::WorkStealing, the queue above is the injector
+    stealing: Option<Stealing>,
+    panic_policy: PanicPolicy,
+    panic_hook: Option<PanicHook>,
+    on_worker_start: Option<WorkerHook>,
+    on_worker_stop: Option<WorkerHook>,
+    // set once a job panicked under PanicPolicy::Poison
+    poisoned: AtomicBool,
+    min_workers: usize,
+    max_workers: usize,
+    // None -> fixed size pool, workers never time out
+    keep_alive: Option<Duration>,
+    // workers whose thread is running (or about to)
+    live: AtomicUsize,
+    // ids keep counting up so a retired worker id is never reused
+    next_id: AtomicUsize,
+    // set by shutdown (or drop), no new jobs are accepted after that
+    closed: AtomicBool,
+    metrics: Metrics,
+    // None -> the pool stays quiet
+    logger: Option<Arc<dyn Logger>>,
+    // only used to tell jobs apart in the logs
+    next_job_id: AtomicU64,
+    thread_options: ThreadOptions,
+    // Some -> workers tell the watchdog which job they run since when
+    watch: Option<JobWatch>,
+    // worker threads that have not returned from Worker::run yet
+    threads: Mutex<usize>,
+    threads_changed: Condvar,
 }
 
 // dyn = dynamic
 // job is a type that implements some traits, but don’t specify what type the return value will be
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+// a job on its way through the queue, queued_at feeds the queue wait histogram
+struct QueuedJob {
+    job: Job,
+    id: u64,
+    queued_at: Instant,
+    // index of the named queue it was submitted to, see JobQueue::queue_index
+    queue: usize,
+    // a cancelled job is skipped when a worker picks it up
+    token: Option<CancellationToken>,
+}
+
 enum Message {
-    NewJob(Job),
+    NewJob(QueuedJob),
     Terminate,
 }
 
+thread_local! {
+    // when the job running on this thread was queued, see ThreadPool::current_queue_wait
+    static CURRENT_QUEUED_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+    // the worker running the job on this thread, CALLER_WORKER when the caller runs it
+    static CURRENT_WORKER: Cell<Option<usize>> = const { Cell::new(None) };
+    // set by Shared::caught_panic: the job returned, but a panic inside it was caught
+    static CAUGHT_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
 impl ThreadPool {
     pub fn new(size: usize) -> ThreadPool {
-        // if false -> assert! call panic!
-        assert!(size > 0); // usize include 0, but create 0 thread has no sense
-        let (sender, receiver) = mpsc::channel();
+        // same as build, but panic! if the pool can not be created
+        match ThreadPool::build(size) {
+            Ok(pool) => pool,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // shorthand for ThreadPoolBuilder::new().size(size).build()
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPoolBuilder::new().size(size).build()
+    }
+
+    pub fn with_config(config: PoolConfig) -> Result<ThreadPool, PoolCreationError> {
+        config.validate()?;
+        let size = config.size;
+
+        // with_capacity would panic on an absurd size -> report it instead
+        let mut workers = Vec::new();
+        if workers.try_reserve_exact(size).is_err() {
+            return Err(PoolCreationError::InvalidConfig(format!(
+                "pool size {} is too large",
+                size
+            )));
+        }
 
-        // multiple producer single consumer
-        // -> Arc: let multiple workers own the receiver
-        // -> Mutex: let only one worker access the receiver at one time
-        let receiver = Arc::new(Mutex::new(receiver));
+        let shared = Arc::new(Shared {
+            workers: Mutex::new(workers),
+            queue: JobQueue::new(
+                config.queue_capacity,
+                config.overflow_policy,
+                config.starvation_limit,
+                config.queues,
+                config.rate_limit,
+                config.queue_rate_limits,
+            ),
+            stealing: match config.scheduler {
+                Scheduler::SharedQueue => None,
+                Scheduler::WorkStealing => Some(Stealing::new()),
+            },
+            panic_policy: config.panic_policy,
+            panic_hook: config.panic_hook,
+            on_worker_start: config.on_worker_start,
+            on_worker_stop: config.on_worker_stop,
+            poisoned: AtomicBool::new(false),
+            min_workers: size,
+            max_workers: config.max_size.unwrap_or(size),
+            keep_alive: config.max_size.map(|_| config.keep_alive),
+            live: AtomicUsize::new(0),
+            next_id: AtomicUsize::new(size),
+            closed: AtomicBool::new(false),
+            metrics: Metrics::default(),
+            logger: config.logger,
+            next_job_id: AtomicU64::new(0),
+            thread_options: ThreadOptions {
+                name_prefix: config.thread_name,
+                stack_size: config.stack_size,
+                cpu_affinity: config.cpu_affinity,
+                nice: config.nice,
+            },
+            watch: config
+                .job_deadline
+                .map(|deadline| JobWatch::new(deadline, config.on_stuck_job)),
+            threads: Mutex::new(0),
+            threads_changed: Condvar::new(),
+        });
+
+        let watchdog = match shared.watch {
+            Some(_) => Some(
+                Watchdog::start(Arc::clone(&shared)).map_err(PoolCreationError::SpawnWatchdog)?,
+            ),
+            None => None,
+        };
 
-        // with_capacity same as new, but more efficient (preallocates space vs resizes when
-        // inserting)
-        let mut workers = Vec::with_capacity(size);
+        let pool = ThreadPool {
+            shared,
+            timer: OnceLock::new(),
+            watchdog,
+        };
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            pool.shared.live.fetch_add(1, Ordering::SeqCst);
+            match Worker::new(id, Arc::clone(&pool.shared)) {
+                Ok(worker) => pool.shared.workers.lock().unwrap().push(worker),
+                // dropping the half built pool terminates and joins the workers started so far
+                Err(source) => {
+                    pool.shared.live.fetch_sub(1, Ordering::SeqCst);
+                    return Err(PoolCreationError::Spawn { id, source });
+                }
+            }
         }
 
-        ThreadPool { workers, sender }
+        Ok(pool)
     }
 
     pub fn execute<F>(&self, f: F)
@@ -46,27 +245,731 @@ impl ThreadPool {
         // lifetime 'static -> don't know lifetime of the thread
         F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
-        self.sender.send(Message::NewJob(job)).unwrap();
+        // the overflow policy already decided what happens to a job that does not fit
+        let _ = self.try_execute(f);
+    }
+
+    // same as execute, but tells the caller when the job was not accepted
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .push_job(Priority::Normal, Box::new(f), None)
+            .map_err(|(error, _)| error)
+    }
+
+    // queued High jobs run before Normal ones, Normal before Low
+    // (a job that waited longer than the starvation limit runs first regardless)
+    pub fn execute_with_priority<F>(&self, priority: Priority, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let _ = self.shared.push_job(priority, Box::new(f), None);
+    }
+
+    // queue f on one of the named queues from PoolConfig::queues, "default" is the one
+    // execute uses; workers take turns between the queues according to their weights
+    pub fn execute_on<F>(&self, queue: &str, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let queue = self
+            .shared
+            .queue
+            .queue_index(queue)
+            .ok_or(ExecuteError::UnknownQueue)?;
+
+        self.shared
+            .push_job_on(queue, Priority::Normal, Box::new(f), None)
+            .map_err(|(error, _)| error)
+    }
+
+    // f does not run if token is cancelled before a worker picks it up
+    // to stop early once running, f has to check a clone of the token itself
+    pub fn execute_cancellable<F>(&self, priority: Priority, token: &CancellationToken, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let _ = self
+            .shared
+            .push_job(priority, Box::new(f), Some(token.clone()));
+    }
+
+    // run f once on a worker after delay, unless the handle is cancelled first
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> TimerHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.timer().schedule_once(delay, Box::new(f))
+    }
+
+    // run f on a worker every interval (first run after one interval) until the handle is
+    // cancelled or the pool is dropped
+    // a tick is skipped while the previous run is still queued or running
+    pub fn execute_every<F>(&self, interval: Duration, f: F) -> TimerHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.timer().schedule_every(interval, Arc::new(f))
+    }
+
+    // stop the pool, see ShutdownMode for what happens to queued jobs
+    pub fn shutdown(mut self, mode: ShutdownMode) -> ShutdownReport {
+        // Drop sees the pool closed and has nothing left to do
+        self.close(mode)
+    }
+
+    fn close(&mut self, mode: ShutdownMode) -> ShutdownReport {
+        let shared = &self.shared;
+        shared.closed.store(true, Ordering::SeqCst);
+        // the timer may be stuck pushing into a full queue of a paused pool, stop would wait
+        // for it forever
+        shared.queue.close();
+
+        // pending timers must not feed jobs into a queue nobody reads anymore
+        if let Some(timer) = self.timer.take() {
+            timer.stop();
+        }
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.stop();
+        }
+
+        let completed = shared.metrics.completed.load(Ordering::SeqCst);
+        let mut report = ShutdownReport::default();
+
+        if mode == ShutdownMode::DiscardQueued {
+            report.discarded += shared.discard_queued();
+        }
+
+        shared.log(
+            Level::Info,
+            "shutting down",
+            &[("mode", format!("{:?}", mode).as_str().into())],
+        );
+
+        // paused workers would never see their terminate message
+        shared.queue.resume();
+
+        // retired workers already left, only the running ones need a terminate message
+        shared.queue.terminate(shared.live.load(Ordering::SeqCst));
+
+        let timed_out = match mode {
+            ShutdownMode::Deadline(timeout) => !shared.wait_for_threads(Instant::now() + timeout),
+            _ => false,
+        };
+        if timed_out {
+            // whatever is still queued will not run anymore
+            report.discarded += shared.discard_queued();
+        }
+
+        let workers = std::mem::take(&mut *shared.workers.lock().unwrap());
+        for worker in workers {
+            // joining a stuck worker would wait forever -> leave it running on its own
+            if timed_out && !worker.is_finished() {
+                shared.log(
+                    Level::Warn,
+                    "worker did not stop in time",
+                    &[("worker", worker.id.into())],
+                );
+                report.unfinished_workers.push(worker.id);
+                continue;
+            }
+
+            // a respawned thread stores its handle before the old one exits -> keep joining
+            // until the slot stays empty
+            // the slot is unlocked before joining, a respawn during shutdown locks it too
+            loop {
+                let thread = worker.thread.lock().unwrap().take();
+                let Some(thread) = thread else { break };
+                if thread.join().is_err() {
+                    shared.log(
+                        Level::Error,
+                        "worker panicked while shutting down",
+                        &[("worker", worker.id.into())],
+                    );
+                }
+            }
+        }
+
+        report.completed = shared.metrics.completed.load(Ordering::SeqCst) - completed;
+        shared.log(
+            Level::Info,
+            "shut down",
+            &[
+                ("completed", report.completed.into()),
+                ("discarded", report.discarded.into()),
+                ("unfinished", report.unfinished_workers.len().into()),
+            ],
+        );
+        report
+    }
+
+    fn timer(&self) -> &Timer {
+        // like the old thread::spawn, a pool that can not start its timer thread panics here
+        self.timer
+            .get_or_init(|| match Timer::start(Arc::clone(&self.shared)) {
+                Ok(timer) => timer,
+                Err(e) => panic!("failed to spawn timer thread: {}", e),
+            })
+    }
+
+    pub fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        // one channel per job to carry the result back to the caller
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::clone(&self.shared);
+
+        self.execute(move || {
+            // catch the panic here so the handle sees it instead of waiting forever
+            let result = panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+                shared.caught_panic(payload.as_ref());
+                JoinError::Panicked(payload)
+            });
+            // the caller may have dropped the handle, nobody wants the result then
+            let _ = sender.send(result);
+        });
+
+        JobHandle::new(receiver)
+    }
+
+    // like std::thread::scope, but the spawned jobs run on the pool's workers
+    // every job spawned through s has finished when scope returns, so they can borrow locals:
+    // pool.scope(|s| for chunk in data.chunks_mut(64) { s.spawn(move || chunk.sort()) })
+    // a panic in f or in any job is re-raised here once all jobs are done
+    // careful when calling this from a job: the worker blocks while waiting, if every worker
+    // does that nobody is left to run the scoped jobs
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        Scope::run(&self.shared, f)
+    }
+
+    // f applied to every item on the workers, results come back in input order
+    // items are handed out in chunks, see parallel::split
+    pub fn map<I, F, R>(&self, items: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> R + Sync,
+        R: Send,
+    {
+        let chunks = parallel::split(items, self.worker_count());
+        // one slot per chunk, each job fills its own
+        let mut results: Vec<Vec<R>> = chunks.iter().map(|_| Vec::new()).collect();
+
+        self.for_each_chunk(
+            chunks.into_iter().zip(results.iter_mut()),
+            |(chunk, out)| {
+                *out = chunk.into_iter().map(&f).collect();
+            },
+        );
+
+        results.into_iter().flatten().collect()
+    }
+
+    pub fn for_each<I, F>(&self, items: I, f: F)
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) + Sync,
+    {
+        let chunks = parallel::split(items, self.worker_count());
+        self.for_each_chunk(chunks, |chunk| chunk.into_iter().for_each(&f));
+    }
+
+    // combine all items with op, every chunk starts from identity()
+    // op must be associative, items are combined in input order but not strictly left to right
+    // pool.reduce(1..=100u64, || 0, |a, b| a + b) == 5050
+    pub fn reduce<I, ID, OP>(&self, items: I, identity: ID, op: OP) -> I::Item
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        ID: Fn() -> I::Item + Sync,
+        OP: Fn(I::Item, I::Item) -> I::Item + Sync,
+    {
+        let chunks = parallel::split(items, self.worker_count());
+        let mut results: Vec<Option<I::Item>> = chunks.iter().map(|_| None).collect();
+
+        self.for_each_chunk(
+            chunks.into_iter().zip(results.iter_mut()),
+            |(chunk, out)| {
+                *out = Some(chunk.into_iter().fold(identity(), &op));
+            },
+        );
+
+        results.into_iter().flatten().fold(identity(), &op)
+    }
+
+    // one scoped job per chunk, a single chunk is not worth a trip through the queue
+    fn for_each_chunk<C, F>(&self, chunks: C, f: F)
+    where
+        C: IntoIterator,
+        C::Item: Send,
+        F: Fn(C::Item) + Sync,
+    {
+        let mut chunks = chunks.into_iter().peekable();
+        let first = match chunks.next() {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        if chunks.peek().is_none() {
+            return f(first);
+        }
+
+        let f = &f;
+        self.scope(|s| {
+            for chunk in std::iter::once(first).chain(chunks) {
+                s.spawn(move || f(chunk));
+            }
+        });
+    }
+
+    // called from inside a job: how long it waited in the queue before a worker picked it up
+    // None outside a pool job
+    // lets a server answer 504 instead of serving a request the client gave up on long ago
+    pub fn current_queue_wait() -> Option<Duration> {
+        CURRENT_QUEUED_AT
+            .with(|queued_at| queued_at.get())
+            .map(|queued_at| queued_at.elapsed())
+    }
+
+    // poll future on the workers, one job per poll: a wake queues the next poll, a pending
+    // future does not hold on to a worker
+    // the handle reports JoinError::Cancelled if the pool goes away before the future finishes
+    pub fn spawn_future<F>(&self, future: F) -> JobHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        task::spawn(&self.shared, future)
+    }
+
+    // workers stop picking up jobs (the ones running finish), submissions still queue up
+    // shutdown and drop resume the pool first, so ShutdownMode::Drain still runs the queue
+    pub fn pause(&self) {
+        if self.shared.queue.pause() {
+            self.shared.log(Level::Info, "paused", &[]);
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.shared.queue.resume() {
+            self.shared.log(Level::Info, "resumed", &[]);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.queue.is_paused()
+    }
+
+    // true once a job panicked while the pool runs with PanicPolicy::Poison
+    pub fn is_poisoned(&self) -> bool {
+        self.shared.poisoned.load(Ordering::SeqCst)
+    }
+
+    pub fn overflow_stats(&self) -> OverflowStats {
+        self.shared.queue.overflow_stats()
+    }
+
+    // a consistent-enough snapshot: every counter is read on its own, without stopping the pool
+    pub fn stats(&self) -> PoolStats {
+        let shared = &self.shared;
+        let metrics = &shared.metrics;
+        let workers = shared.live.load(Ordering::SeqCst);
+        let active_workers = metrics.busy.load(Ordering::Relaxed).min(workers);
+
+        let mut queues = shared.queue.queue_stats();
+        // jobs moved to a local deque are still waiting, they count for the queue they came from
+        if let Some(stealing) = &shared.stealing {
+            let local = stealing.queued_by_queue(queues.len());
+            for (queue, local) in queues.iter_mut().zip(local) {
+                queue.queued += local;
+            }
+        }
+
+        PoolStats {
+            queued: queues.iter().map(|queue| queue.queued).sum(),
+            workers,
+            active_workers,
+            idle_workers: workers - active_workers,
+            completed: metrics.completed.load(Ordering::Relaxed),
+            panicked: metrics.panicked.load(Ordering::Relaxed),
+            cancelled: metrics.cancelled.load(Ordering::Relaxed),
+            stuck: metrics.stuck.load(Ordering::Relaxed),
+            queue_wait: metrics.queue_wait.snapshot(),
+            execution_time: metrics.execution_time.snapshot(),
+            overflow: shared.queue.overflow_stats(),
+            paused: shared.queue.is_paused(),
+            paused_time: shared.queue.paused_time(),
+            queues,
+            rate_limit: shared.queue.rate_limit_stats(),
+        }
+    }
+
+    // number of workers currently running, changes over time for an elastic pool
+    pub fn worker_count(&self) -> usize {
+        self.shared.live.load(Ordering::SeqCst)
     }
 }
 
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        println!("Sending terminate message to all workers.");
+impl Shared {
+    // Err hands the job back so the caller can decide what to do with work that was not accepted
+    fn push_job(
+        self: &Arc<Shared>,
+        priority: Priority,
+        job: Job,
+        token: Option<CancellationToken>,
+    ) -> Result<(), (ExecuteError, Job)> {
+        self.push_job_on(DEFAULT_QUEUE, priority, job, token)
+    }
+
+    fn push_job_on(
+        self: &Arc<Shared>,
+        queue: usize,
+        priority: Priority,
+        job: Job,
+        token: Option<CancellationToken>,
+    ) -> Result<(), (ExecuteError, Job)> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err((ExecuteError::ShutDown, job));
+        }
+
+        if self.poisoned.load(Ordering::SeqCst) {
+            return Err((ExecuteError::Poisoned, job));
+        }
+
+        let job = QueuedJob {
+            job,
+            id: self.next_job_id.fetch_add(1, Ordering::Relaxed),
+            queued_at: Instant::now(),
+            queue,
+            token,
+        };
+
+        // jobs spawned by a job stay on the local deque of the worker running it, unless they
+        // asked for a priority or a named queue: only the shared queue knows about those
+        // same when the shared queue has to see every job, see JobQueue::allows_local
+        if let (Some(stealing), Priority::Normal, DEFAULT_QUEUE, true) =
+            (&self.stealing, priority, queue, self.queue.allows_local())
+        {
+            if let Some(local) = stealing.current() {
+                if !self.queue.admit(queue) {
+                    return Err((ExecuteError::RateLimited, job.job));
+                }
+                stealing.push_local(&local, job, &self.queue);
+                self.grow_if_backed_up();
+                return Ok(());
+            }
+        }
+
+        match self.queue.push(priority, job) {
+            Ok(()) => {
+                self.grow_if_backed_up();
+                Ok(())
+            }
+            // same treatment as on a worker: a panic must not unwind into the submitter, which
+            // may be the server's accept loop or the timer thread
+            Err((ExecuteError::QueueFull, queued))
+                if self.queue.policy == OverflowPolicy::CallerRuns =>
+            {
+                self.run_job(None, queued);
+                Ok(())
+            }
+            Err((error, queued)) => Err((error, queued.job)),
+        }
+    }
+
+    // elastic pool: one more worker when jobs are waiting and nobody is idle to take them
+    fn grow_if_backed_up(self: &Arc<Shared>) {
+        let shared = self;
+        if shared.live.load(Ordering::SeqCst) >= shared.max_workers {
+            return;
+        }
+        let local = shared
+            .stealing
+            .as_ref()
+            .map_or(0, |stealing| stealing.len());
+        if !shared.queue.is_backed_up(local) {
+            return;
+        }
+
+        // reserve the slot first so two submitters can not both go past max_workers
+        let reserved = shared
+            .live
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                (live < shared.max_workers).then_some(live + 1)
+            });
+        if reserved.is_err() {
+            return;
+        }
+
+        let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut workers = shared.workers.lock().unwrap();
+        // forget the handles of workers that already retired
+        workers.retain(|worker| !worker.is_finished());
+
+        match Worker::new(id, Arc::clone(shared)) {
+            Ok(worker) => workers.push(worker),
+            Err(e) => {
+                shared.live.fetch_sub(1, Ordering::SeqCst);
+                shared.log(
+                    Level::Warn,
+                    "worker could not be spawned",
+                    &[
+                        ("worker", id.into()),
+                        ("error", e.to_string().as_str().into()),
+                    ],
+                );
+            }
+        }
+    }
+
+    // None -> idle for longer than keep_alive
+    fn next_message(&self, local: Option<&LocalQueue>) -> Option<Message> {
+        loop {
+            // a paused pool leaves the deques alone too, pop below waits for resume
+            if let (Some(stealing), Some(local), false) =
+                (&self.stealing, local, self.queue.is_paused())
+            {
+                if let Some(job) = stealing.find_job(local, &self.queue) {
+                    return Some(Message::NewJob(job));
+                }
+            }
+
+            let stealable = || self.stealing.as_ref().is_some_and(|s| s.has_stealable());
+            match self.queue.pop(self.keep_alive, &stealable) {
+                Pop::Message(message) => return Some(message),
+                Pop::TimedOut => return None,
+                // another worker's deque has jobs -> go steal them
+                Pop::Woken => continue,
+            }
+        }
+    }
+
+    // drop every queued job, returns how many there were
+    fn discard_queued(&self) -> usize {
+        let local = self
+            .stealing
+            .as_ref()
+            .map_or(0, |stealing| stealing.clear());
+        self.queue.clear() + local
+    }
+
+    // false -> some worker threads were still running at the deadline
+    fn wait_for_threads(&self, deadline: Instant) -> bool {
+        let mut threads = self.threads.lock().unwrap();
+
+        while *threads > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            threads = self
+                .threads_changed
+                .wait_timeout(threads, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        true
+    }
+
+    fn log(&self, level: Level, message: &str, fields: &[(&str, Value<'_>)]) {
+        if let Some(logger) = &self.logger {
+            if logger.enabled(level) {
+                logger.log(&Record {
+                    level,
+                    target: "pool",
+                    message,
+                    fields,
+                });
+            }
+        }
+    }
+
+    fn thread_exited(&self) {
+        *self.threads.lock().unwrap() -= 1;
+        self.threads_changed.notify_all();
+    }
 
-        for _ in &mut self.workers {
-            self.sender.send(Message::Terminate).unwrap();
+    // run on_worker_start / on_worker_stop, which one is in `event` for the log
+    fn worker_hook(&self, hook: &Option<WorkerHook>, id: usize, event: &str) {
+        if let Some(hook) = hook {
+            // same as the panic hook: the worker survives a broken hook, the log says so
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| hook(id))) {
+                self.log(
+                    Level::Error,
+                    "worker hook panicked",
+                    &[
+                        ("worker", id.into()),
+                        ("hook", event.into()),
+                        (
+                            "panic",
+                            handle::payload_message(payload.as_ref())
+                                .unwrap_or("<non-string payload>")
+                                .into(),
+                        ),
+                    ],
+                );
+            }
+        }
+    }
+
+    // run one job with the bookkeeping every job gets: panic caught, metrics, logs, watchdog
+    // worker None -> the submitting thread runs it (OverflowPolicy::CallerRuns)
+    // true -> the job panicked
+    fn run_job(&self, worker: Option<usize>, queued: QueuedJob) -> bool {
+        let QueuedJob {
+            job,
+            id: job_id,
+            queued_at,
+            queue,
+            token,
+        } = queued;
+        let worker_field: Value<'_> = match worker {
+            Some(id) => id.into(),
+            None => "caller".into(),
+        };
+
+        // a poisoned pool drops the job without running it
+        if self.poisoned.load(Ordering::SeqCst) {
+            self.log(
+                Level::Debug,
+                "job dropped, pool is poisoned",
+                &[("worker", worker_field), ("job", job_id.into())],
+            );
+            return false;
+        }
+
+        if token.is_some_and(|token| token.is_cancelled()) {
+            self.metrics.cancelled.fetch_add(1, Ordering::Relaxed);
+            self.log(
+                Level::Debug,
+                "job skipped, cancelled while queued",
+                &[("worker", worker_field), ("job", job_id.into())],
+            );
+            return false;
         }
 
-        println!("Shutting down all workers.");
+        self.log(
+            Level::Trace,
+            "job started",
+            &[("worker", worker_field), ("job", job_id.into())],
+        );
+
+        let metrics = &self.metrics;
+        let started = Instant::now();
+        metrics.queue_wait.record(started - queued_at);
+        if let Some(id) = worker {
+            metrics.busy.fetch_add(1, Ordering::Relaxed);
+            if let Some(watch) = &self.watch {
+                watch.started(id, job_id);
+            }
+        }
+        // the caller may be a worker running a job of its own, put its value back afterwards
+        let outer = CURRENT_QUEUED_AT.with(|current| current.replace(Some(queued_at)));
+        let outer_worker =
+            CURRENT_WORKER.with(|current| current.replace(Some(worker.unwrap_or(CALLER_WORKER))));
+        let outer_caught = CAUGHT_PANIC.with(|caught| caught.replace(false));
 
-        for worker in &mut self.workers {
-            println!("Shutting down worker {}", worker.id);
+        // catch the panic per job so one bad job does not kill the whole thread
+        let result = panic::catch_unwind(AssertUnwindSafe(job));
 
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+        CURRENT_QUEUED_AT.with(|current| current.set(outer));
+        CURRENT_WORKER.with(|current| current.set(outer_worker));
+        let caught = CAUGHT_PANIC.with(|caught| caught.replace(outer_caught));
+        if let Some(id) = worker {
+            if let Some(watch) = &self.watch {
+                watch.finished(id);
             }
+            metrics.busy.fetch_sub(1, Ordering::Relaxed);
+        }
+        metrics.execution_time.record(started.elapsed());
+        metrics.completed.fetch_add(1, Ordering::SeqCst);
+        self.queue.job_finished(queue);
+
+        self.log(
+            Level::Trace,
+            "job finished",
+            &[("worker", worker_field), ("job", job_id.into())],
+        );
+
+        // caught -> hook, policy and metrics were done by caught_panic, the worker still has to
+        // follow PanicPolicy::Respawn
+        let payload = match result {
+            Ok(()) => return caught,
+            Err(payload) => payload,
+        };
+
+        self.log(
+            Level::Warn,
+            "job panicked",
+            &[
+                ("worker", worker_field),
+                ("job", job_id.into()),
+                (
+                    "panic",
+                    handle::payload_message(payload.as_ref())
+                        .unwrap_or("<non-string payload>")
+                        .into(),
+                ),
+            ],
+        );
+        self.job_panicked(worker.unwrap_or(CALLER_WORKER), payload.as_ref());
+        true
+    }
+
+    // submit, groups, scopes, graphs and futures catch the panic themselves to hand it to the
+    // caller, they report it here so the hook, PanicPolicy and stats still see it
+    pub(crate) fn caught_panic(&self, payload: &(dyn Any + Send)) {
+        let worker = CURRENT_WORKER.with(|current| current.get());
+        // outside a job (a scoped job run inline) nobody reads the flag
+        CAUGHT_PANIC.with(|caught| caught.set(true));
+
+        let worker_field: Value<'_> = match worker {
+            Some(CALLER_WORKER) | None => "caller".into(),
+            Some(id) => id.into(),
+        };
+        self.log(
+            Level::Warn,
+            "job panicked",
+            &[
+                ("worker", worker_field),
+                (
+                    "panic",
+                    handle::payload_message(payload)
+                        .unwrap_or("<non-string payload>")
+                        .into(),
+                ),
+            ],
+        );
+        self.job_panicked(worker.unwrap_or(CALLER_WORKER), payload);
+    }
+
+    fn job_panicked(&self, id: usize, payload: &(dyn Any + Send)) {
+        self.metrics.panicked.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(hook) = &self.panic_hook {
+            // a panicking hook must not take the worker thread down with it
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(id, payload)));
+        }
+
+        if self.panic_policy == PanicPolicy::Poison {
+            self.poisoned.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // shutdown already did the work
+        if !self.shared.closed.load(Ordering::SeqCst) {
+            self.close(ShutdownMode::Drain);
         }
     }
 }
@@ -76,29 +979,695 @@ struct Worker {
     id: usize,
     // The spawn function returns a JoinHandle<T> -> try to use it
     // () because this is the closure does not return anything
-    thread: Option<thread::JoinHandle<()>>,
+    // Arc<Mutex<..>> because a respawned thread replaces the handle from inside the worker
+    thread: ThreadSlot,
 }
 
+type ThreadSlot = Arc<Mutex<Option<thread::JoinHandle<()>>>>;
+
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv().unwrap();
+    fn new(id: usize, shared: Arc<Shared>) -> io::Result<Worker> {
+        let thread = Arc::new(Mutex::new(None));
+        Worker::spawn(id, shared, &thread)?;
+
+        Ok(Worker { id, thread })
+    }
+
+    fn is_finished(&self) -> bool {
+        match &*self.thread.lock().unwrap() {
+            Some(thread) => thread.is_finished(),
+            None => true,
+        }
+    }
+
+    fn spawn(id: usize, shared: Arc<Shared>, slot: &ThreadSlot) -> io::Result<()> {
+        // hold the slot while spawning so the new thread can not respawn before its own handle
+        // is stored
+        let mut current = slot.lock().unwrap();
+        let own_slot = Arc::clone(slot);
+        let counter = Arc::clone(&shared);
+
+        // counted before the thread starts so shutdown never sees a gap while respawning
+        *counter.threads.lock().unwrap() += 1;
+
+        // the new thread reports whether pinning / nice worked before it takes any job
+        let (ready, started) = mpsc::sync_channel(1);
+
+        // thread::spawn panics when the OS can not create a thread, Builder::spawn returns the error
+        let spawned = counter.thread_options.builder(id).spawn(move || {
+            if let Err(e) = shared.thread_options.setup_worker(id) {
+                let _ = ready.send(Err(e));
+                shared.thread_exited();
+                return;
+            }
+
+            let _ = ready.send(Ok(()));
+            Worker::run(id, shared, own_slot);
+        });
+
+        let thread = match spawned {
+            Ok(thread) => thread,
+            Err(e) => {
+                counter.thread_exited();
+                return Err(e);
+            }
+        };
+
+        match started.recv() {
+            Ok(Ok(())) => {
+                *current = Some(thread);
+                Ok(())
+            }
+            // the thread already gave up and counted itself out
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            // the thread died (a panic in setup) before it could count itself out
+            Err(_) => {
+                let _ = thread.join();
+                counter.thread_exited();
+                Err(io::Error::other("worker thread exited during setup"))
+            }
+        }
+    }
+
+    fn run(id: usize, shared: Arc<Shared>, slot: ThreadSlot) {
+        let local = shared.stealing.as_ref().map(|stealing| stealing.register());
+        shared.worker_hook(&shared.on_worker_start, id, "start");
+        let mut respawn = false;
+
+        loop {
+            let message = match shared.next_message(local.as_deref()) {
+                Some(message) => message,
+                // idle for longer than keep_alive -> retire if the pool stays above its minimum
+                None => {
+                    let retired =
+                        shared
+                            .live
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                                (live > shared.min_workers).then_some(live - 1)
+                            });
+                    if retired.is_ok() {
+                        shared.log(Level::Debug, "worker retiring", &[("worker", id.into())]);
+                        break;
+                    }
+                    continue;
+                }
+            };
 
             match message {
                 Message::NewJob(job) => {
-                    println!("Worker {} got a job; executing.", id);
-                    job();
+                    let panicked = shared.run_job(Some(id), job);
+
+                    if panicked && shared.panic_policy == PanicPolicy::Respawn {
+                        respawn = true;
+                        break;
+                    }
                 }
                 Message::Terminate => {
-                    println!("Worker {} was told to terminate.", id);
+                    shared.log(Level::Debug, "worker terminating", &[("worker", id.into())]);
                     break;
                 }
             }
+        }
+
+        shared.worker_hook(&shared.on_worker_stop, id, "stop");
+        if let (Some(stealing), Some(local)) = (&shared.stealing, &local) {
+            stealing.unregister(local, &shared.queue);
+        }
+
+        // only now: the new thread runs on_worker_start with the same id, whatever the stop
+        // hook tears down for it must be gone by then
+        if respawn {
+            if let Err(e) = Worker::spawn(id, Arc::clone(&shared), &slot) {
+                shared.log(
+                    Level::Error,
+                    "worker could not be respawned",
+                    &[
+                        ("worker", id.into()),
+                        ("error", e.to_string().as_str().into()),
+                    ],
+                );
+                shared.live.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        shared.thread_exited();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{mpsc, Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        handle, ExecuteError, JoinError, OverflowPolicy, PanicPolicy, Priority, RateLimit,
+        RateLimitPolicy, Scheduler, ShutdownMode, ThreadPool, ThreadPoolBuilder, CALLER_WORKER,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // a job that says so on started once it runs, then holds its worker until release is used
+    // or dropped
+    pub(crate) fn blocking_job(
+        started: mpsc::Sender<()>,
+    ) -> (impl FnOnce() + Send + 'static, mpsc::Sender<()>) {
+        let (release, blocked) = mpsc::channel::<()>();
+        let job = move || {
+            let _ = started.send(());
+            let _ = blocked.recv();
+        };
+        (job, release)
+    }
+
+    // keep a worker of pool busy until the returned sender is used or dropped
+    pub(crate) fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+        let (started_sender, started) = mpsc::channel();
+        let (job, release) = blocking_job(started_sender);
+        pool.execute(job);
+        started.recv_timeout(TIMEOUT).unwrap();
+        release
+    }
+
+    fn pool_with(policy: PanicPolicy) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .size(1)
+            .panic_policy(policy)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn keep_worker_runs_later_jobs_on_the_same_thread() {
+        let pool = pool_with(PanicPolicy::KeepWorker);
+
+        let before = pool.submit(|| thread::current().id()).join().unwrap();
+        pool.execute(|| panic!("boom"));
+        let after = pool.submit(|| thread::current().id()).join().unwrap();
+
+        assert_eq!(before, after);
+        assert_eq!(pool.stats().panicked, 1);
+        assert_eq!(pool.worker_count(), 1);
+    }
+
+    #[test]
+    fn respawn_replaces_the_thread() {
+        let pool = pool_with(PanicPolicy::Respawn);
+
+        let before = pool.submit(|| thread::current().id()).join().unwrap();
+        pool.execute(|| panic!("boom"));
+        let after = pool.submit(|| thread::current().id()).join().unwrap();
+
+        assert_ne!(before, after);
+        assert_eq!(pool.worker_count(), 1);
+    }
+
+    #[test]
+    fn the_pool_grows_under_a_backlog_and_shrinks_when_idle() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .max_size(3)
+            .keep_alive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        // three jobs that only finish together: each one needs a worker of its own
+        let (started, all_started) = mpsc::channel();
+        let mut releases = Vec::new();
+        for _ in 0..3 {
+            let (release, released) = mpsc::channel::<()>();
+            let started = started.clone();
+            pool.execute(move || {
+                started.send(()).unwrap();
+                let _ = released.recv();
+            });
+            releases.push(release);
+        }
+        for _ in 0..3 {
+            all_started.recv_timeout(TIMEOUT).unwrap();
+        }
+        assert_eq!(pool.worker_count(), 3);
+
+        // no more than max_size, however long the backlog gets
+        pool.execute(|| {});
+        assert_eq!(pool.worker_count(), 3);
+
+        drop(releases);
+        let deadline = Instant::now() + TIMEOUT;
+        while pool.worker_count() > 1 {
+            assert!(
+                Instant::now() < deadline,
+                "pool did not shrink back to size"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pool.submit(|| 1).join().unwrap(), 1);
+    }
+
+    #[test]
+    fn drop_while_a_respawn_job_is_panicking() {
+        let pool = pool_with(PanicPolicy::Respawn);
+        let (started, job_started) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            // panic once drop is already joining this worker
+            thread::sleep(Duration::from_millis(100));
+            panic!("boom");
         });
+        job_started.recv_timeout(TIMEOUT).unwrap();
 
-        Worker {
-            id,
-            thread: Some(thread),
+        // drop on its own thread so a deadlock fails the test instead of hanging it
+        let (dropped, pool_dropped) = mpsc::channel();
+        thread::spawn(move || {
+            drop(pool);
+            dropped.send(()).unwrap();
+        });
+
+        pool_dropped.recv_timeout(TIMEOUT).unwrap();
+    }
+
+    #[test]
+    fn poison_refuses_new_jobs() {
+        let pool = pool_with(PanicPolicy::Poison);
+
+        let (sender, receiver) = mpsc::channel();
+        pool.execute(move || {
+            let _ = sender.send(());
+            panic!("boom");
+        });
+        receiver.recv_timeout(TIMEOUT).unwrap();
+        let deadline = Instant::now() + TIMEOUT;
+        while !pool.is_poisoned() {
+            assert!(Instant::now() < deadline, "pool was not poisoned");
+            thread::yield_now();
         }
+
+        assert_eq!(pool.try_execute(|| {}), Err(ExecuteError::Poisoned));
+    }
+
+    #[test]
+    fn panic_hook_gets_the_worker_and_the_payload() {
+        let seen = Arc::new(Mutex::new(None));
+        let (sender, receiver) = mpsc::channel();
+        let hook_seen = Arc::clone(&seen);
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .panic_hook(move |worker, payload| {
+                let message = handle::payload_message(payload).map(str::to_string);
+                *hook_seen.lock().unwrap() = Some((worker, message));
+                let _ = sender.send(());
+            })
+            .build()
+            .unwrap();
+
+        pool.execute(|| panic!("boom"));
+        receiver.recv_timeout(TIMEOUT).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), Some((0, Some("boom".to_string()))));
+    }
+
+    #[test]
+    fn a_panic_caught_for_a_handle_still_reaches_hook_policy_and_stats() {
+        let seen = Arc::new(Mutex::new(None));
+        let hook_seen = Arc::clone(&seen);
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .panic_policy(PanicPolicy::Poison)
+            .panic_hook(move |worker, payload| {
+                let message = handle::payload_message(payload).map(str::to_string);
+                *hook_seen.lock().unwrap() = Some((worker, message));
+            })
+            .build()
+            .unwrap();
+
+        let result = pool.submit(|| panic!("boom")).join();
+
+        assert!(matches!(result, Err(JoinError::Panicked(_))));
+        assert_eq!(*seen.lock().unwrap(), Some((0, Some("boom".to_string()))));
+        assert_eq!(pool.stats().panicked, 1);
+        assert!(pool.is_poisoned());
+    }
+
+    #[test]
+    fn caller_runs_catches_the_panic() {
+        let (hook_sender, hook_receiver) = mpsc::channel();
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue_capacity(1)
+            .overflow_policy(OverflowPolicy::CallerRuns)
+            .panic_hook(move |worker, _| {
+                let _ = hook_sender.send(worker);
+            })
+            .build()
+            .unwrap();
+
+        // keep the worker busy and the queue full
+        let release = block_worker(&pool);
+        pool.execute(|| {});
+
+        let caller = thread::current().id();
+        let ran_on = Arc::new(Mutex::new(None));
+        let job_ran_on = Arc::clone(&ran_on);
+        pool.execute(move || {
+            *job_ran_on.lock().unwrap() = Some(thread::current().id());
+            panic!("boom");
+        });
+
+        assert_eq!(*ran_on.lock().unwrap(), Some(caller));
+        assert_eq!(hook_receiver.recv_timeout(TIMEOUT).unwrap(), CALLER_WORKER);
+        let stats = pool.stats();
+        assert_eq!(stats.panicked, 1);
+        assert_eq!(stats.overflow.caller_runs, 1);
+
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn a_flooded_queue_leaves_the_other_queues_their_slots() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue("tenant", 1)
+            .queue_capacity(2)
+            .overflow_policy(OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+        let release = block_worker(&pool);
+
+        let accepted = (0..10).filter(|_| pool.try_execute(|| {}).is_ok()).count();
+
+        assert_eq!(accepted, 2);
+        assert_eq!(pool.execute_on("tenant", || {}), Ok(()));
+        assert_eq!(pool.execute_on("tenant", || {}), Ok(()));
+        assert_eq!(
+            pool.execute_on("tenant", || {}),
+            Err(ExecuteError::QueueFull)
+        );
+        drop(release);
+    }
+
+    #[test]
+    fn a_blocked_flood_does_not_block_another_queue() {
+        let pool = Arc::new(
+            ThreadPoolBuilder::new()
+                .size(1)
+                .queue("tenant", 1)
+                .queue_capacity(1)
+                .build()
+                .unwrap(),
+        );
+        let release = block_worker(&pool);
+
+        // fills "default" and then waits for a slot that only frees up after release
+        let flooding = Arc::clone(&pool);
+        let flood = thread::spawn(move || {
+            for _ in 0..3 {
+                flooding.execute(|| {});
+            }
+        });
+        let deadline = Instant::now() + TIMEOUT;
+        while pool.overflow_stats().blocked == 0 {
+            assert!(Instant::now() < deadline, "the flood never blocked");
+            thread::yield_now();
+        }
+
+        assert_eq!(pool.execute_on("tenant", || {}), Ok(()));
+        drop(release);
+        flood.join().unwrap();
+    }
+
+    #[test]
+    fn jobs_from_a_worker_respect_the_queue_capacity() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .scheduler(Scheduler::WorkStealing)
+            .queue_capacity(2)
+            .overflow_policy(OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+
+        // a scoped job runs on the worker and can still borrow the pool
+        let mut accepted = 0;
+        pool.scope(|s| {
+            s.spawn(|| accepted = (0..100).filter(|_| pool.try_execute(|| {}).is_ok()).count());
+        });
+
+        assert_eq!(accepted, 2);
+    }
+
+    // queue every job on a paused one worker pool, the order they ran in
+    fn run_order(pool: ThreadPool, jobs: &[(Priority, &'static str)]) -> Vec<&'static str> {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        pool.pause();
+        for &(priority, name) in jobs {
+            let order = Arc::clone(&order);
+            pool.execute_with_priority(priority, move || order.lock().unwrap().push(name));
+            // distinct queued_at for the starvation check
+            thread::sleep(Duration::from_millis(30));
+        }
+        pool.resume();
+        pool.shutdown(ShutdownMode::Drain);
+
+        Arc::try_unwrap(order).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn higher_priorities_run_first() {
+        let order = run_order(
+            ThreadPool::new(1),
+            &[
+                (Priority::Low, "low"),
+                (Priority::Normal, "normal"),
+                (Priority::High, "high"),
+            ],
+        );
+
+        assert_eq!(order, ["high", "normal", "low"]);
+    }
+
+    #[test]
+    fn a_starved_job_runs_before_a_newer_high_one() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .starvation_limit(Duration::from_millis(20))
+            .build()
+            .unwrap();
+
+        let order = run_order(pool, &[(Priority::Low, "low"), (Priority::High, "high")]);
+
+        assert_eq!(order, ["low", "high"]);
+    }
+
+    #[test]
+    fn a_paused_pool_runs_nothing_until_resumed() {
+        let pool = ThreadPool::new(1);
+        assert!(!pool.is_paused());
+        assert_eq!(pool.stats().paused_time, Duration::ZERO);
+
+        pool.pause();
+        // twice is the same as once
+        pool.pause();
+        let (sender, ran) = mpsc::channel();
+        pool.execute(move || sender.send(()).unwrap());
+
+        assert!(pool.is_paused());
+        assert!(ran.recv_timeout(Duration::from_millis(30)).is_err());
+        assert!(pool.stats().paused_time >= Duration::from_millis(30));
+
+        pool.resume();
+        ran.recv_timeout(TIMEOUT).unwrap();
+        assert!(!pool.is_paused());
+        // the clock stops with the pause
+        let paused_time = pool.stats().paused_time;
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(pool.stats().paused_time, paused_time);
+    }
+
+    #[test]
+    fn drop_while_the_timer_waits_on_a_full_paused_queue() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue_capacity(1)
+            .build()
+            .unwrap();
+        pool.pause();
+        pool.execute(|| {});
+        pool.execute_every(Duration::from_millis(5), || {});
+        // the first tick is stuck pushing into the full queue by now
+        thread::sleep(Duration::from_millis(30));
+
+        // drop on its own thread so a hang fails the test instead of hanging it
+        let (dropped, pool_dropped) = mpsc::channel();
+        thread::spawn(move || {
+            drop(pool);
+            dropped.send(()).unwrap();
+        });
+
+        pool_dropped.recv_timeout(TIMEOUT).unwrap();
+    }
+
+    // paused -> every job is still queued when shutdown starts
+    fn paused_pool_with_jobs(jobs: usize) -> ThreadPool {
+        let pool = ThreadPool::new(2);
+        pool.pause();
+        for _ in 0..jobs {
+            pool.execute(|| {});
+        }
+        pool
+    }
+
+    #[test]
+    fn drain_runs_every_queued_job() {
+        let report = paused_pool_with_jobs(5).shutdown(ShutdownMode::Drain);
+
+        assert_eq!(report.completed, 5);
+        assert_eq!(report.discarded, 0);
+        assert!(report.unfinished_workers.is_empty());
+    }
+
+    #[test]
+    fn discard_queued_drops_every_queued_job() {
+        let report = paused_pool_with_jobs(5).shutdown(ShutdownMode::DiscardQueued);
+
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.discarded, 5);
+        assert!(report.unfinished_workers.is_empty());
+    }
+
+    #[test]
+    fn deadline_leaves_a_stuck_worker_behind() {
+        let pool = ThreadPool::new(1);
+        let release = block_worker(&pool);
+        for _ in 0..3 {
+            pool.execute(|| {});
+        }
+
+        let report = pool.shutdown(ShutdownMode::Deadline(Duration::from_millis(50)));
+
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.discarded, 3);
+        assert_eq!(report.unfinished_workers, vec![0]);
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn respawn_stops_the_old_thread_before_starting_the_new_one() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (start_events, stop_events) = (Arc::clone(&events), Arc::clone(&events));
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .panic_policy(PanicPolicy::Respawn)
+            .on_worker_start(move |id| {
+                start_events
+                    .lock()
+                    .unwrap()
+                    .push(("start", id, thread::current().id()));
+            })
+            .on_worker_stop(move |id| {
+                stop_events
+                    .lock()
+                    .unwrap()
+                    .push(("stop", id, thread::current().id()));
+            })
+            .build()
+            .unwrap();
+
+        pool.execute(|| panic!("boom"));
+        pool.submit(|| {}).join().unwrap();
+
+        let events = events.lock().unwrap().clone();
+        let kinds: Vec<_> = events.iter().map(|(kind, id, _)| (*kind, *id)).collect();
+        assert_eq!(kinds, [("start", 0), ("stop", 0), ("start", 0)]);
+        assert_eq!(events[0].2, events[1].2);
+        assert_ne!(events[1].2, events[2].2);
+    }
+
+    #[test]
+    fn jobs_in_local_deques_count_for_their_queue() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .scheduler(Scheduler::WorkStealing)
+            .queue("batch", 1)
+            .build()
+            .unwrap();
+
+        let (started_sender, started) = mpsc::channel();
+        let (first, first_release) = blocking_job(started_sender.clone());
+        let (second, second_release) = blocking_job(started_sender);
+
+        pool.execute_on("batch", first).unwrap();
+        started.recv_timeout(TIMEOUT).unwrap();
+        pool.execute_on("batch", second).unwrap();
+        for _ in 0..3 {
+            pool.execute_on("batch", || {}).unwrap();
+        }
+        // the worker takes half of the 4 queued jobs: runs the second blocker, keeps one
+        first_release.send(()).unwrap();
+        started.recv_timeout(TIMEOUT).unwrap();
+
+        let stats = pool.stats();
+        let batch = stats.queues.iter().find(|q| q.name == "batch").unwrap();
+        assert_eq!(batch.queued, 3);
+        assert_eq!(stats.queued, 3);
+
+        second_release.send(()).unwrap();
+    }
+
+    #[test]
+    fn jobs_from_a_worker_wait_for_the_rate_limit() {
+        let pool = ThreadPoolBuilder::new()
+            .size(2)
+            .scheduler(Scheduler::WorkStealing)
+            .rate_limit(RateLimit::per_second(50).burst(1))
+            .build()
+            .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let started = Instant::now();
+        pool.scope(|s| {
+            s.spawn(|| {
+                for _ in 0..6 {
+                    let sender = sender.clone();
+                    pool.execute(move || sender.send(()).unwrap());
+                }
+            });
+        });
+        for _ in 0..6 {
+            receiver.recv_timeout(TIMEOUT).unwrap();
+        }
+
+        // the scoped job took the only token, the six after it come 20ms apart
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn a_job_refused_for_a_full_queue_keeps_its_token() {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue_capacity(1)
+            .overflow_policy(OverflowPolicy::Reject)
+            // no refill worth mentioning during the test
+            .rate_limit(RateLimit {
+                per_second: 0.001,
+                burst: 3,
+                policy: RateLimitPolicy::Reject,
+            })
+            .build()
+            .unwrap();
+
+        let release = block_worker(&pool);
+        let (done_sender, done) = mpsc::channel();
+        pool.execute(move || done_sender.send(()).unwrap());
+
+        assert_eq!(pool.try_execute(|| {}), Err(ExecuteError::QueueFull));
+
+        release.send(()).unwrap();
+        done.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(pool.try_execute(|| {}), Ok(()));
+        assert_eq!(pool.try_execute(|| {}), Err(ExecuteError::RateLimited));
     }
 }
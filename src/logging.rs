@@ -0,0 +1,364 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// most severe first, so `level <= max_level` means "log it"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+// value of a structured field, kept typed so JSON output can tell numbers from strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    U64(u64),
+    I64(i64),
+    Bool(bool),
+    Str(&'a str),
+}
+
+impl From<u64> for Value<'_> {
+    fn from(value: u64) -> Self {
+        Value::U64(value)
+    }
+}
+
+impl From<usize> for Value<'_> {
+    fn from(value: usize) -> Self {
+        Value::U64(value as u64)
+    }
+}
+
+impl From<i64> for Value<'_> {
+    fn from(value: i64) -> Self {
+        Value::I64(value)
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::U64(value) => write!(f, "{}", value),
+            Value::I64(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+// one log event: "pool" for the thread pool itself, whatever the application likes otherwise
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    pub message: &'a str,
+    pub fields: &'a [(&'a str, Value<'a>)],
+}
+
+// plug in your own to route pool diagnostics into an existing logging setup
+pub trait Logger: Send + Sync {
+    // checked before a record is built, so disabled levels cost next to nothing
+    fn enabled(&self, level: Level) -> bool;
+
+    fn log(&self, record: &Record<'_>);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    // 1760000000.123 info  pool: job finished worker=2 job=17
+    #[default]
+    Text,
+    // {"ts":1760000000.123,"level":"info","target":"pool","msg":"job finished","worker":2,"job":17}
+    JsonLines,
+}
+
+// writes one line per record to stderr, a file, or any other Write
+pub struct WriterLogger {
+    max_level: Level,
+    format: LogFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl WriterLogger {
+    pub fn new(out: Box<dyn Write + Send>, format: LogFormat, max_level: Level) -> WriterLogger {
+        WriterLogger {
+            max_level,
+            format,
+            out: Mutex::new(out),
+        }
+    }
+
+    pub fn stderr(format: LogFormat, max_level: Level) -> WriterLogger {
+        WriterLogger::new(Box::new(io::stderr()), format, max_level)
+    }
+
+    // appends to the file, creating it if needed
+    pub fn file(
+        path: impl AsRef<Path>,
+        format: LogFormat,
+        max_level: Level,
+    ) -> io::Result<WriterLogger> {
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(WriterLogger::new(Box::new(file), format, max_level))
+    }
+
+    fn format(&self, record: &Record<'_>) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let ts = format!("{}.{:03}", now.as_secs(), now.subsec_millis());
+        let mut line = String::new();
+
+        // writing into a String can not fail
+        match self.format {
+            LogFormat::Text => {
+                let _ = write!(
+                    line,
+                    "{} {:<5} {}: {}",
+                    ts, record.level, record.target, record.message
+                );
+                for (key, value) in record.fields {
+                    match value {
+                        Value::Str(s) if s.is_empty() || s.contains([' ', '"', '=']) => {
+                            let _ = write!(line, " {}={:?}", key, s);
+                        }
+                        _ => {
+                            let _ = write!(line, " {}={}", key, value);
+                        }
+                    }
+                }
+            }
+            LogFormat::JsonLines => {
+                let _ = write!(
+                    line,
+                    "{{\"ts\":{},\"level\":\"{}\",\"target\":",
+                    ts,
+                    record.level.as_str()
+                );
+                json_string(&mut line, record.target);
+                line.push_str(",\"msg\":");
+                json_string(&mut line, record.message);
+                for (key, value) in record.fields {
+                    line.push(',');
+                    json_string(&mut line, key);
+                    line.push(':');
+                    match value {
+                        Value::Str(s) => json_string(&mut line, s),
+                        _ => {
+                            let _ = write!(line, "{}", value);
+                        }
+                    }
+                }
+                line.push('}');
+            }
+        }
+
+        line.push('\n');
+        line
+    }
+}
+
+impl Logger for WriterLogger {
+    fn enabled(&self, level: Level) -> bool {
+        level <= self.max_level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.level) {
+            return;
+        }
+
+        let line = self.format(record);
+        // nowhere left to report a failing log write
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = out.write_all(line.as_bytes());
+        let _ = out.flush();
+    }
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use super::{json_string, Level, LogFormat, Logger, Record, Value, WriterLogger};
+
+    // a Vec<u8> the test can still read after handing it to the logger
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // every line logged, with the timestamp cut off
+    fn lines(format: LogFormat, max_level: Level, records: &[Record<'_>]) -> Vec<String> {
+        let buffer = Buffer::default();
+        let logger = WriterLogger::new(Box::new(buffer.clone()), format, max_level);
+        for record in records {
+            logger.log(record);
+        }
+
+        let out = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        out.lines()
+            .map(|line| match format {
+                LogFormat::Text => line.split_once(' ').unwrap().1.to_string(),
+                LogFormat::JsonLines => line.split_once(',').unwrap().1.to_string(),
+            })
+            .collect()
+    }
+
+    fn record<'a>(level: Level, fields: &'a [(&'a str, Value<'a>)]) -> Record<'a> {
+        Record {
+            level,
+            target: "pool",
+            message: "job finished",
+            fields,
+        }
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        let mut out = String::new();
+        json_string(&mut out, "a \"b\" \\ \n\r\t \u{1} é");
+
+        assert_eq!(out, r#""a \"b\" \\ \n\r\t \u0001 é""#);
+    }
+
+    #[test]
+    fn json_lines_keep_numbers_and_escape_strings() {
+        let fields = [
+            ("worker", Value::from(2usize)),
+            ("offset", Value::from(-1i64)),
+            ("ok", Value::from(true)),
+            ("panic", Value::from("said \"no\"\n")),
+        ];
+
+        let lines = lines(
+            LogFormat::JsonLines,
+            Level::Info,
+            &[record(Level::Info, &fields)],
+        );
+
+        assert_eq!(
+            lines,
+            [concat!(
+                r#""level":"info","target":"pool","msg":"job finished","#,
+                r#""worker":2,"offset":-1,"ok":true,"panic":"said \"no\"\n"}"#
+            )]
+        );
+    }
+
+    #[test]
+    fn text_quotes_strings_that_need_it() {
+        let fields = [
+            ("plain", Value::from("GET")),
+            ("spaces", Value::from("GET /sleep")),
+            ("quote", Value::from("a\"b")),
+            ("equals", Value::from("a=b")),
+            ("empty", Value::from("")),
+            ("worker", Value::from(2usize)),
+        ];
+
+        let lines = lines(
+            LogFormat::Text,
+            Level::Info,
+            &[record(Level::Info, &fields)],
+        );
+
+        assert_eq!(
+            lines,
+            [concat!(
+                "info  pool: job finished plain=GET spaces=\"GET /sleep\" ",
+                "quote=\"a\\\"b\" equals=\"a=b\" empty=\"\" worker=2"
+            )]
+        );
+    }
+
+    #[test]
+    fn levels_above_max_level_are_dropped() {
+        let records = [
+            record(Level::Error, &[]),
+            record(Level::Warn, &[]),
+            record(Level::Info, &[]),
+            record(Level::Debug, &[]),
+            record(Level::Trace, &[]),
+        ];
+
+        let lines = lines(LogFormat::Text, Level::Warn, &records);
+
+        assert_eq!(
+            lines,
+            ["error pool: job finished", "warn  pool: job finished"]
+        );
+        let logger = WriterLogger::new(Box::new(Buffer::default()), LogFormat::Text, Level::Warn);
+        assert!(logger.enabled(Level::Warn));
+        assert!(!logger.enabled(Level::Info));
+    }
+}
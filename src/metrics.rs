@@ -0,0 +1,235 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::{OverflowStats, QueueStats, RateLimitStats};
+
+// one bucket per power of two microseconds, the last one also takes everything longer
+// (2^31 µs is about 36 minutes)
+const BUCKETS: usize = 32;
+
+// snapshot returned by ThreadPool::stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    // jobs waiting to be picked up by a worker
+    pub queued: usize,
+    pub workers: usize,
+    // workers running a job right now
+    pub active_workers: usize,
+    pub idle_workers: usize,
+    // jobs that ran, panicked ones included
+    pub completed: u64,
+    // panics caught for a JobHandle, group, scope or graph included
+    pub panicked: u64,
+    // jobs skipped because their CancellationToken was cancelled while they were queued
+    pub cancelled: u64,
+    // jobs the watchdog caught running past PoolConfig::job_deadline
+    pub stuck: u64,
+    // time from submission until a worker picked the job up
+    pub queue_wait: Histogram,
+    // time a worker spent running the job
+    pub execution_time: Histogram,
+    pub overflow: OverflowStats,
+    pub paused: bool,
+    // how long the pool was paused in total, the current pause included
+    pub paused_time: Duration,
+    // one entry per named queue, "default" first
+    pub queues: Vec<QueueStats>,
+    pub rate_limit: RateLimitStats,
+}
+
+// latency distribution with power of two buckets, precise enough for dashboards and cheap to
+// record (one atomic add per bucket and one for the sum)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; BUCKETS],
+    total_micros: u64,
+}
+
+impl Histogram {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            count => Some(Duration::from_micros(self.total_micros / count)),
+        }
+    }
+
+    // upper bound of the bucket holding the q-th quantile (q between 0.0 and 1.0)
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, bucket_count) in self.counts.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return Some(bucket_bound(bucket));
+            }
+        }
+
+        Some(bucket_bound(BUCKETS - 1))
+    }
+
+    // (upper bound, count) for every bucket, shortest first
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| (bucket_bound(bucket), *count))
+    }
+}
+
+fn bucket_bound(bucket: usize) -> Duration {
+    Duration::from_micros(1 << bucket)
+}
+
+// the live counters behind PoolStats, every field is a plain atomic so recording never locks
+#[derive(Default)]
+pub(crate) struct Metrics {
+    pub(crate) completed: AtomicU64,
+    pub(crate) panicked: AtomicU64,
+    pub(crate) cancelled: AtomicU64,
+    pub(crate) stuck: AtomicU64,
+    pub(crate) busy: AtomicUsize,
+    pub(crate) queue_wait: AtomicHistogram,
+    pub(crate) execution_time: AtomicHistogram,
+}
+
+pub(crate) struct AtomicHistogram {
+    counts: [AtomicU64; BUCKETS],
+    total_micros: AtomicU64,
+}
+
+impl Default for AtomicHistogram {
+    fn default() -> AtomicHistogram {
+        AtomicHistogram {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+            total_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+impl AtomicHistogram {
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        // bucket i holds durations below 2^i µs
+        let bucket = ((u64::BITS - micros.leading_zeros()) as usize).min(BUCKETS - 1);
+
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Histogram {
+        Histogram {
+            counts: std::array::from_fn(|bucket| self.counts[bucket].load(Ordering::Relaxed)),
+            total_micros: self.total_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{AtomicHistogram, Histogram};
+    use crate::{tests, ThreadPool};
+
+    fn histogram(durations: &[Duration]) -> Histogram {
+        let histogram = AtomicHistogram::default();
+        for &duration in durations {
+            histogram.record(duration);
+        }
+        histogram.snapshot()
+    }
+
+    // upper bound of the only bucket holding duration
+    fn bucket_of(duration: Duration) -> Duration {
+        let histogram = histogram(&[duration]);
+        let mut used = histogram.buckets().filter(|(_, count)| *count > 0);
+        let (bound, _) = used.next().unwrap();
+        assert!(used.next().is_none());
+        bound
+    }
+
+    #[test]
+    fn bucket_boundaries() {
+        let micros = Duration::from_micros;
+
+        assert_eq!(bucket_of(micros(0)), micros(1));
+        assert_eq!(bucket_of(micros(1)), micros(2));
+        assert_eq!(bucket_of(micros(3)), micros(4));
+        assert_eq!(bucket_of(micros(4)), micros(8));
+        // the last bucket takes 2^31 µs and everything longer
+        assert_eq!(bucket_of(micros(1 << 30)), micros(1 << 31));
+        assert_eq!(bucket_of(micros(1 << 31)), micros(1 << 31));
+        assert_eq!(bucket_of(Duration::from_secs(10 * 3600)), micros(1 << 31));
+    }
+
+    #[test]
+    fn an_empty_histogram_has_no_mean_or_percentile() {
+        let histogram = histogram(&[]);
+
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.mean(), None);
+        assert_eq!(histogram.percentile(0.5), None);
+    }
+
+    #[test]
+    fn percentile_and_mean() {
+        let micros = Duration::from_micros;
+        let histogram = histogram(&[micros(1), micros(1), micros(1), micros(100)]);
+
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.mean(), Some(micros(103 / 4)));
+        assert_eq!(histogram.percentile(0.0), Some(micros(2)));
+        assert_eq!(histogram.percentile(0.75), Some(micros(2)));
+        assert_eq!(histogram.percentile(0.76), Some(micros(128)));
+        assert_eq!(histogram.percentile(1.0), Some(micros(128)));
+    }
+
+    #[test]
+    fn stats_snapshot() {
+        let pool = ThreadPool::new(1);
+        let release = tests::block_worker(&pool);
+        pool.execute(|| {});
+        pool.execute(|| {});
+        pool.execute(|| panic!("boom"));
+
+        let stats = pool.stats();
+        assert_eq!(stats.workers, 1);
+        assert_eq!(stats.active_workers, 1);
+        assert_eq!(stats.idle_workers, 0);
+        assert_eq!(stats.queued, 3);
+        assert_eq!(stats.queues[0].name, "default");
+        assert_eq!(stats.queues[0].queued, 3);
+        assert_eq!(stats.completed, 0);
+        assert_eq!(stats.queue_wait.count(), 1);
+        assert_eq!(stats.execution_time.count(), 0);
+
+        drop(release);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.stats().completed < 4 {
+            assert!(Instant::now() < deadline, "jobs did not finish");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.active_workers, 0);
+        assert_eq!(stats.idle_workers, 1);
+        assert_eq!(stats.queued, 0);
+        assert_eq!(stats.panicked, 1);
+        assert_eq!(stats.queue_wait.count(), 4);
+        assert_eq!(stats.execution_time.count(), 4);
+    }
+}
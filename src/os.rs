@@ -0,0 +1,87 @@
+use std::{fmt, io, thread};
+
+// how worker (and timer) threads are created, taken from PoolConfig
+pub(crate) struct ThreadOptions {
+    pub(crate) name_prefix: Option<String>,
+    pub(crate) stack_size: Option<usize>,
+    // worker id -> cpus[id % cpus.len()]
+    pub(crate) cpu_affinity: Option<Vec<usize>>,
+    pub(crate) nice: Option<i32>,
+}
+
+impl ThreadOptions {
+    // thread names show up in top -H, gdb and panic messages as "<prefix>-<suffix>"
+    pub(crate) fn builder(&self, suffix: impl fmt::Display) -> thread::Builder {
+        let mut builder = thread::Builder::new();
+
+        if let Some(prefix) = &self.name_prefix {
+            builder = builder.name(format!("{}-{}", prefix, suffix));
+        }
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+
+        builder
+    }
+
+    // runs on the new worker thread before it takes its first job
+    pub(crate) fn setup_worker(&self, id: usize) -> io::Result<()> {
+        if let Some(cpus) = &self.cpu_affinity {
+            pin_to_cpu(cpus[id % cpus.len()])?;
+        }
+        if let Some(nice) = self.nice {
+            set_nice(nice)?;
+        }
+
+        Ok(())
+    }
+}
+
+// cpu_set_t has room for this many cpus, CPU_SET panics on a higher index
+#[cfg(target_os = "linux")]
+pub(crate) const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+
+#[cfg(target_os = "linux")]
+fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    // SAFETY: cpu_set_t is plain data, CPU_ZERO / CPU_SET only write inside it, and pid 0 means
+    // the calling thread
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(cpu, &mut set);
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: plain syscalls without pointers, on Linux the nice value of a tid only affects
+    // that one thread
+    unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        if libc::setpriority(libc::PRIO_PROCESS, tid, nice) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+// PoolConfig::validate refuses these options elsewhere, so these are never reached
+#[cfg(not(target_os = "linux"))]
+pub(crate) const MAX_CPUS: usize = usize::MAX;
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_cpu(_cpu: usize) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
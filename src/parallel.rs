@@ -0,0 +1,108 @@
+// helpers behind ThreadPool::map, for_each and reduce
+
+// a few chunks per worker: a worker that got the cheap items takes another chunk instead of
+// idling while the others finish
+const CHUNKS_PER_WORKER: usize = 4;
+
+// cut items into at most workers * CHUNKS_PER_WORKER chunks of equal size (the last may be
+// shorter), so a million small items become a few dozen jobs instead of a million
+pub(crate) fn split<I: IntoIterator>(items: I, workers: usize) -> Vec<Vec<I::Item>> {
+    let items: Vec<I::Item> = items.into_iter().collect();
+    let chunk_len = items
+        .len()
+        .div_ceil(workers.max(1) * CHUNKS_PER_WORKER)
+        .max(1);
+
+    let mut items = items.into_iter();
+    let mut chunks = Vec::new();
+    loop {
+        let chunk: Vec<I::Item> = items.by_ref().take(chunk_len).collect();
+        if chunk.is_empty() {
+            return chunks;
+        }
+        chunks.push(chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        thread,
+    };
+
+    use super::split;
+    use crate::ThreadPool;
+
+    #[test]
+    fn split_makes_a_few_chunks_per_worker() {
+        let chunks = split(0..100, 2);
+
+        assert_eq!(chunks.len(), 8);
+        assert!(chunks[..7].iter().all(|chunk| chunk.len() == 13));
+        assert_eq!(chunks[7].len(), 9);
+        assert_eq!(chunks.concat(), (0..100).collect::<Vec<_>>());
+        assert!(split(0..0, 2).is_empty());
+    }
+
+    #[test]
+    fn map_keeps_the_input_order() {
+        let pool = ThreadPool::new(4);
+
+        let doubled = pool.map(0..1000u64, |x| x * 2);
+
+        assert_eq!(doubled, (0..1000u64).map(|x| x * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_input() {
+        let pool = ThreadPool::new(2);
+        let calls = AtomicUsize::new(0);
+
+        let mapped: Vec<u32> = pool.map(Vec::<u32>::new(), |x| x);
+        pool.for_each(Vec::<u32>::new(), |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+        });
+        let sum = pool.reduce(Vec::<u32>::new(), || 7, |a, b| a + b);
+
+        assert!(mapped.is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(sum, 7);
+    }
+
+    #[test]
+    fn a_single_chunk_runs_on_the_caller() {
+        let pool = ThreadPool::new(2);
+        let caller = thread::current().id();
+
+        let ran_on = pool.map([1], |_| thread::current().id());
+
+        assert_eq!(ran_on, [caller]);
+    }
+
+    #[test]
+    fn for_each_visits_every_item_once() {
+        let pool = ThreadPool::new(4);
+        let seen = Mutex::new(Vec::new());
+
+        pool.for_each(0..500, |x| seen.lock().unwrap().push(x));
+
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort();
+        assert_eq!(seen, (0..500).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reduce_combines_in_input_order() {
+        let pool = ThreadPool::new(4);
+        let words: Vec<String> = (0..50).map(|i| i.to_string()).collect();
+
+        let joined = pool.reduce(words.clone(), String::new, |a, b| a + &b);
+
+        assert_eq!(joined, words.concat());
+        assert_eq!(pool.reduce(1..=100u64, || 0, |a, b| a + b), 5050);
+    }
+}
@@ -0,0 +1,622 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    rate::TokenBucket, ExecuteError, Message, OverflowPolicy, QueuedJob, RateLimit,
+    RateLimitPolicy, RateLimitStats,
+};
+
+// the queue execute, submit and friends use, always index 0 in QueueState::queues
+pub(crate) const DEFAULT_QUEUE: usize = 0;
+pub(crate) const DEFAULT_QUEUE_NAME: &str = "default";
+
+// workers waiting for a rate limit token never sleep shorter than this
+const MIN_THROTTLE_WAIT: Duration = Duration::from_millis(1);
+
+// which jobs a worker picks first, see ThreadPool::execute_with_priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    // index into QueueState::lanes, highest first
+    fn lane(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+// what happened to submissions that found the queue full, see ThreadPool::overflow_stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OverflowStats {
+    // callers that had to wait for a free slot (OverflowPolicy::Block)
+    pub blocked: u64,
+    // jobs refused (OverflowPolicy::Reject)
+    pub rejected: u64,
+    // jobs run on the submitting thread (OverflowPolicy::CallerRuns)
+    pub caller_runs: u64,
+    // queued jobs thrown away to make room (OverflowPolicy::DropOldest)
+    pub dropped_oldest: u64,
+}
+
+// per named queue numbers, part of PoolStats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    pub name: String,
+    pub weight: u32,
+    // jobs waiting in this queue, the ones in local deques (Scheduler::WorkStealing) included
+    pub queued: usize,
+    // jobs from this queue that ran, panicked ones included
+    pub completed: u64,
+}
+
+// replaces the mpsc channel: a plain channel can not be bounded and drop its oldest entry at
+// the same time
+pub(crate) struct JobQueue {
+    state: Mutex<QueueState>,
+    // workers wait here for jobs
+    not_empty: Condvar,
+    // blocked submitters wait here for a free slot
+    not_full: Condvar,
+    capacity: Option<usize>,
+    pub(crate) policy: OverflowPolicy,
+    // see allows_local
+    allows_local: bool,
+    // a lower priority job that waited this long goes before any higher priority one
+    starvation_limit: Duration,
+    blocked: AtomicU64,
+    rejected: AtomicU64,
+    caller_runs: AtomicU64,
+    dropped_oldest: AtomicU64,
+    // workers currently waiting in pop, only changed while holding the state lock but atomic so
+    // wake_idle can skip the lock when nobody sleeps
+    idle: AtomicUsize,
+    // name of every queue in QueueState::queues, fixed once the pool is built
+    names: Vec<String>,
+    // per queue, counted by the worker after the job ran
+    completed: Vec<AtomicU64>,
+    rate_delayed: AtomicU64,
+    rate_rejected: AtomicU64,
+}
+
+// what a worker got out of pop
+pub(crate) enum Pop {
+    Message(Message),
+    // nothing arrived within the idle timeout
+    TimedOut,
+    // the wake condition passed to pop became true (work waits somewhere else)
+    Woken,
+}
+
+// one named queue, see PoolConfig::queues
+struct Tenant {
+    // one FIFO per priority, index 0 is Priority::High
+    lanes: [VecDeque<QueuedJob>; 3],
+    // jobs in all lanes
+    len: usize,
+    // jobs taken per round, see QueueState::pick
+    weight: u32,
+    // PoolConfig::queue_rate_limits
+    limit: Option<TokenBucket>,
+}
+
+impl Tenant {
+    // highest priority first, unless a lower lane has a job waiting past the starvation limit
+    fn pop_front(&mut self, starvation_limit: Duration) -> Option<QueuedJob> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let now = Instant::now();
+        // the oldest starving job wins, otherwise the first non-empty lane
+        let starving = (1..self.lanes.len())
+            .filter_map(|lane| Some((lane, self.lanes[lane].front()?.queued_at)))
+            .filter(|(_, queued_at)| now.duration_since(*queued_at) >= starvation_limit)
+            .min_by_key(|(_, queued_at)| *queued_at)
+            .map(|(lane, _)| lane);
+        let lane = starving.or_else(|| self.lanes.iter().position(|jobs| !jobs.is_empty()))?;
+
+        self.len -= 1;
+        self.lanes[lane].pop_front()
+    }
+}
+
+struct QueueState {
+    queues: Vec<Tenant>,
+    // jobs in all queues
+    len: usize,
+    // the queue whose turn it is and how many more jobs it may hand out this round
+    current: usize,
+    credit: u32,
+    // PoolConfig::rate_limit, shared by all queues
+    limit: Option<TokenBucket>,
+    // terminate messages are counted instead of queued, so they never take a slot and
+    // DropOldest can not throw them away
+    terminate: usize,
+    // Some -> paused since then, pop hands out nothing until resume
+    paused_since: Option<Instant>,
+    // earlier pauses added up, the current one not included
+    paused_total: Duration,
+    // set by close, a push waiting for a free slot gives up
+    closed: bool,
+}
+
+impl QueueState {
+    fn push_back(&mut self, priority: Priority, job: QueuedJob) {
+        let queue = &mut self.queues[job.queue];
+        queue.lanes[priority.lane()].push_back(job);
+        queue.len += 1;
+        self.len += 1;
+    }
+
+    // weighted round robin (deficit round robin with every job costing 1): a queue with weight 3
+    // hands out up to 3 jobs before the next non-empty queue gets its turn, an empty queue
+    // loses the rest of its turn so a burst can not build up credit
+    // a queue out of RateLimitPolicy::Wait tokens is passed over, None -> every queue with
+    // jobs is throttled (or there are none)
+    fn pick(&mut self, now: Instant) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        if bucket(&mut self.limit, RateLimitPolicy::Wait).is_some_and(|b| b.available(now) == 0) {
+            return None;
+        }
+
+        // checking the current queue and then every queue with fresh credit covers one round
+        for _ in 0..=self.queues.len() {
+            let queue = &mut self.queues[self.current];
+            let allowed = bucket(&mut queue.limit, RateLimitPolicy::Wait)
+                .is_none_or(|b| b.available(now) > 0);
+
+            if self.credit > 0 && queue.len > 0 && allowed {
+                self.credit -= 1;
+                if let Some(b) = bucket(&mut queue.limit, RateLimitPolicy::Wait) {
+                    b.take(now);
+                }
+                if let Some(b) = bucket(&mut self.limit, RateLimitPolicy::Wait) {
+                    b.take(now);
+                }
+                return Some(self.current);
+            }
+            self.current = (self.current + 1) % self.queues.len();
+            self.credit = self.queues[self.current].weight;
+        }
+
+        None
+    }
+
+    // the RateLimitPolicy::Reject limits a job for `queue` falls under all have a token left
+    fn admits(&mut self, queue: usize, now: Instant) -> bool {
+        bucket(&mut self.limit, RateLimitPolicy::Reject).is_none_or(|b| b.available(now) > 0)
+            && bucket(&mut self.queues[queue].limit, RateLimitPolicy::Reject)
+                .is_none_or(|b| b.available(now) > 0)
+    }
+
+    // take the tokens admits checked, once the job is accepted
+    fn charge(&mut self, queue: usize, now: Instant) {
+        if let Some(b) = bucket(&mut self.limit, RateLimitPolicy::Reject) {
+            b.take(now);
+        }
+        if let Some(b) = bucket(&mut self.queues[queue].limit, RateLimitPolicy::Reject) {
+            b.take(now);
+        }
+    }
+
+    // Some -> jobs are queued but held back by a rate limit, this long until one may go
+    fn throttled_for(&mut self, now: Instant) -> Option<Duration> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let pool = bucket(&mut self.limit, RateLimitPolicy::Wait)
+            .map_or(Duration::ZERO, |b| b.next_token(now));
+        let queue = self
+            .queues
+            .iter_mut()
+            .filter(|queue| queue.len > 0)
+            .map(|queue| {
+                bucket(&mut queue.limit, RateLimitPolicy::Wait)
+                    .map_or(Duration::ZERO, |b| b.next_token(now))
+            })
+            .min()
+            .unwrap_or(Duration::ZERO);
+
+        Some(pool.max(queue).max(MIN_THROTTLE_WAIT))
+    }
+
+    fn pop_front(&mut self, starvation_limit: Duration) -> Option<QueuedJob> {
+        let queue = self.pick(Instant::now())?;
+        let job = self.queues[queue].pop_front(starvation_limit)?;
+        self.len -= 1;
+        Some(job)
+    }
+
+    // the oldest job of the lowest priority in queue, the one we care about least
+    // the other queues keep their jobs, their slots are their own
+    fn drop_oldest(&mut self, queue: usize) {
+        let tenant = &mut self.queues[queue];
+        if let Some(jobs) = tenant.lanes.iter_mut().rev().find(|jobs| !jobs.is_empty()) {
+            jobs.pop_front();
+            tenant.len -= 1;
+            self.len -= 1;
+        }
+    }
+}
+
+// the bucket of a limit, if it has the given policy
+fn bucket(limit: &mut Option<TokenBucket>, policy: RateLimitPolicy) -> Option<&mut TokenBucket> {
+    limit.as_mut().filter(|b| b.policy() == policy)
+}
+
+impl JobQueue {
+    pub(crate) fn new(
+        capacity: Option<usize>,
+        policy: OverflowPolicy,
+        starvation_limit: Duration,
+        // (name, weight) of every named queue, DEFAULT_QUEUE_NAME only sets the default's weight
+        named: Vec<(String, u32)>,
+        rate_limit: Option<RateLimit>,
+        // validated to name known queues
+        queue_rate_limits: Vec<(String, RateLimit)>,
+    ) -> JobQueue {
+        let mut queues = vec![(DEFAULT_QUEUE_NAME.to_string(), 1)];
+        for (name, weight) in named {
+            match queues.iter_mut().find(|(known, _)| *known == name) {
+                Some(queue) => queue.1 = weight,
+                None => queues.push((name, weight)),
+            }
+        }
+
+        // a Wait limit on the default queue, or on all of them, is applied when a worker takes
+        // a job from the shared queue
+        let waits = |limit: Option<&RateLimit>| {
+            limit.is_some_and(|limit| limit.policy == RateLimitPolicy::Wait)
+        };
+        let default_limit = queue_rate_limits
+            .iter()
+            .find(|(limited, _)| limited == DEFAULT_QUEUE_NAME)
+            .map(|(_, limit)| limit);
+        let allows_local =
+            capacity.is_none() && !waits(rate_limit.as_ref()) && !waits(default_limit);
+
+        JobQueue {
+            state: Mutex::new(QueueState {
+                queues: queues
+                    .iter()
+                    .map(|(name, weight)| Tenant {
+                        lanes: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+                        len: 0,
+                        weight: *weight,
+                        limit: queue_rate_limits
+                            .iter()
+                            .find(|(limited, _)| limited == name)
+                            .map(|(_, limit)| TokenBucket::new(*limit)),
+                    })
+                    .collect(),
+                len: 0,
+                current: DEFAULT_QUEUE,
+                credit: queues[DEFAULT_QUEUE].1,
+                limit: rate_limit.map(TokenBucket::new),
+                terminate: 0,
+                paused_since: None,
+                paused_total: Duration::ZERO,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            policy,
+            allows_local,
+            starvation_limit,
+            blocked: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            caller_runs: AtomicU64::new(0),
+            dropped_oldest: AtomicU64::new(0),
+            idle: AtomicUsize::new(0),
+            completed: queues.iter().map(|_| AtomicU64::new(0)).collect(),
+            rate_delayed: AtomicU64::new(0),
+            rate_rejected: AtomicU64::new(0),
+            names: queues.into_iter().map(|(name, _)| name).collect(),
+        }
+    }
+
+    // take a token from every RateLimitPolicy::Reject limit a job for `queue` falls under,
+    // for jobs that skip push (local deques)
+    // false -> one of them is used up, the job must be refused
+    pub(crate) fn admit(&self, queue: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if !state.admits(queue, now) {
+            self.rate_rejected.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        state.charge(queue, now);
+        true
+    }
+
+    // false -> every job has to go through push: capacity and overflow policy (bounded queue)
+    // and RateLimitPolicy::Wait limits on the default queue are only applied here
+    pub(crate) fn allows_local(&self) -> bool {
+        self.allows_local
+    }
+
+    pub(crate) fn rate_limit_stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            delayed: self.rate_delayed.load(Ordering::Relaxed),
+            rejected: self.rate_rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn queue_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|known| known == name)
+    }
+
+    pub(crate) fn job_finished(&self, queue: usize) {
+        self.completed[queue].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn queue_stats(&self) -> Vec<QueueStats> {
+        let state = self.state.lock().unwrap();
+
+        self.names
+            .iter()
+            .zip(&state.queues)
+            .zip(&self.completed)
+            .map(|((name, queue), completed)| QueueStats {
+                name: name.clone(),
+                weight: queue.weight,
+                queued: queue.len,
+                completed: completed.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    // Err gives the job back when it may not be queued: QueueFull under Reject and CallerRuns,
+    // RateLimited when a RateLimitPolicy::Reject limit is used up, ShutDown when the queue
+    // got closed while waiting under Block
+    pub(crate) fn push(
+        &self,
+        priority: Priority,
+        job: QueuedJob,
+    ) -> Result<(), (ExecuteError, QueuedJob)> {
+        let mut state = self.state.lock().unwrap();
+        let mut blocked = false;
+
+        // checked again after every wait for a free slot, others may have used the tokens
+        loop {
+            if !state.admits(job.queue, Instant::now()) {
+                self.rate_rejected.fetch_add(1, Ordering::Relaxed);
+                return Err((ExecuteError::RateLimited, job));
+            }
+
+            // every queue has capacity slots of its own, a burst on one does not fill the others
+            let queued = state.queues[job.queue].len;
+            let full = self.capacity.is_some_and(|capacity| queued >= capacity);
+            if !full {
+                break;
+            }
+
+            match self.policy {
+                OverflowPolicy::Block => {
+                    if state.closed {
+                        return Err((ExecuteError::ShutDown, job));
+                    }
+                    if !blocked {
+                        self.blocked.fetch_add(1, Ordering::Relaxed);
+                        blocked = true;
+                    }
+                    state = self.not_full.wait(state).unwrap();
+                }
+                OverflowPolicy::Reject => {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err((ExecuteError::QueueFull, job));
+                }
+                OverflowPolicy::CallerRuns => {
+                    // the job still runs, on the caller: it uses up its tokens all the same
+                    state.charge(job.queue, Instant::now());
+                    self.caller_runs.fetch_add(1, Ordering::Relaxed);
+                    return Err((ExecuteError::QueueFull, job));
+                }
+                OverflowPolicy::DropOldest => {
+                    self.dropped_oldest.fetch_add(1, Ordering::Relaxed);
+                    state.drop_oldest(job.queue);
+                }
+            }
+        }
+        let now = Instant::now();
+        state.charge(job.queue, now);
+
+        // more jobs than tokens -> this one will wait for the bucket to refill
+        let queued = state.len;
+        let tenant = &mut state.queues[job.queue];
+        let tenant_queued = tenant.len;
+        let delayed = bucket(&mut tenant.limit, RateLimitPolicy::Wait)
+            .is_some_and(|b| b.available(now) <= tenant_queued)
+            || bucket(&mut state.limit, RateLimitPolicy::Wait)
+                .is_some_and(|b| b.available(now) <= queued);
+        if delayed {
+            self.rate_delayed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        state.push_back(priority, job);
+        self.not_empty.notify_one();
+
+        Ok(())
+    }
+
+    // block until there is a job or a terminate message, queued jobs go first
+    pub(crate) fn pop(&self, idle_timeout: Option<Duration>, wake: &dyn Fn() -> bool) -> Pop {
+        let deadline = idle_timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            // paused workers just sleep: no job, no terminate, no idle timeout, no wake
+            // condition until resume notifies them
+            if state.paused_since.is_some() {
+                state = self.not_empty.wait(state).unwrap();
+                continue;
+            }
+
+            if let Some(job) = state.pop_front(self.starvation_limit) {
+                // the waiting pushers may be after another queue's slot
+                self.not_full.notify_all();
+                return Pop::Message(Message::NewJob(job));
+            }
+
+            // throttled jobs still count as queued, a draining pool waits for them
+            if state.terminate > 0 && state.len == 0 {
+                state.terminate -= 1;
+                return Pop::Message(Message::Terminate);
+            }
+
+            // count ourselves idle before checking wake, whoever makes wake true checks idle
+            // afterwards -> one of the two always sees the other
+            self.idle.fetch_add(1, Ordering::SeqCst);
+            if wake() {
+                self.idle.fetch_sub(1, Ordering::SeqCst);
+                return Pop::Woken;
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                self.idle.fetch_sub(1, Ordering::SeqCst);
+                return Pop::TimedOut;
+            }
+
+            // no idle timeout and nothing throttled -> sleep until notified
+            let throttled = state.throttled_for(now).map(|wait| now + wait);
+            state = match deadline.into_iter().chain(throttled).min() {
+                Some(until) => self.not_empty.wait_timeout(state, until - now).unwrap().0,
+                None => self.not_empty.wait(state).unwrap(),
+            };
+            self.idle.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // take up to half of the queued jobs (at most max) without waiting
+    pub(crate) fn try_pop_batch(&self, max: usize) -> VecDeque<QueuedJob> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.len.div_ceil(2).min(max);
+        let batch: VecDeque<QueuedJob> = (0..count)
+            .filter_map(|_| state.pop_front(self.starvation_limit))
+            .collect();
+
+        if !batch.is_empty() {
+            self.not_full.notify_all();
+        }
+
+        batch
+    }
+
+    // put a job back at the front, ignoring the capacity: it was accepted once already
+    // only jobs from local deques come back, and those all had Priority::Normal
+    pub(crate) fn requeue(&self, job: QueuedJob) {
+        let mut state = self.state.lock().unwrap();
+        let queue = &mut state.queues[job.queue];
+        queue.lanes[Priority::Normal.lane()].push_front(job);
+        queue.len += 1;
+        state.len += 1;
+        self.not_empty.notify_one();
+    }
+
+    // false -> was paused already
+    pub(crate) fn pause(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.paused_since.is_some() {
+            return false;
+        }
+        state.paused_since = Some(Instant::now());
+        true
+    }
+
+    // false -> was not paused
+    pub(crate) fn resume(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let since = match state.paused_since.take() {
+            Some(since) => since,
+            None => return false,
+        };
+        state.paused_total += since.elapsed();
+        self.not_empty.notify_all();
+        true
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused_since.is_some()
+    }
+
+    // total time spent paused, the pause going on right now included
+    pub(crate) fn paused_time(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        state.paused_total
+            + state
+                .paused_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    // drop every queued job, returns how many there were
+    pub(crate) fn clear(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let count = state.len;
+
+        for queue in state.queues.iter_mut() {
+            for jobs in queue.lanes.iter_mut() {
+                jobs.clear();
+            }
+            queue.len = 0;
+        }
+        state.len = 0;
+        self.not_full.notify_all();
+
+        count
+    }
+
+    // wake one waiting worker so it re-checks its wake condition
+    pub(crate) fn wake_idle(&self) {
+        if self.idle.load(Ordering::SeqCst) > 0 {
+            let _state = self.state.lock().unwrap();
+            self.not_empty.notify_one();
+        }
+    }
+
+    // more jobs waiting (here and `elsewhere`, the local deques) than workers waiting for them
+    // never while paused: more workers would not run anything either
+    pub(crate) fn is_backed_up(&self, elsewhere: usize) -> bool {
+        let state = self.state.lock().unwrap();
+        state.paused_since.is_none() && state.len + elsewhere > self.idle.load(Ordering::SeqCst)
+    }
+
+    // pushes blocked on a full queue return ShutDown, and so do later ones that would block
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn terminate(&self, count: usize) {
+        self.state.lock().unwrap().terminate += count;
+        self.not_empty.notify_all();
+    }
+
+    pub(crate) fn overflow_stats(&self) -> OverflowStats {
+        OverflowStats {
+            blocked: self.blocked.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            caller_runs: self.caller_runs.load(Ordering::Relaxed),
+            dropped_oldest: self.dropped_oldest.load(Ordering::Relaxed),
+        }
+    }
+}
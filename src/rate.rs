@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+// what happens to a job that finds the rate limit used up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitPolicy {
+    // queue it anyway, workers leave it there until a token is available
+    #[default]
+    Wait,
+    // refuse it, try_execute / execute_on return ExecuteError::RateLimited
+    Reject,
+}
+
+// token bucket: `per_second` tokens flow in every second, at most `burst` are kept
+// every job takes one, see PoolConfig::rate_limit and PoolConfig::queue_rate_limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+    pub policy: RateLimitPolicy,
+}
+
+impl RateLimit {
+    // n jobs per second, up to n at once after a quiet period, excess jobs wait
+    pub fn per_second(n: u32) -> RateLimit {
+        RateLimit {
+            per_second: n as f64,
+            burst: n.max(1),
+            policy: RateLimitPolicy::default(),
+        }
+    }
+
+    pub fn burst(mut self, burst: u32) -> RateLimit {
+        self.burst = burst;
+        self
+    }
+
+    pub fn policy(mut self, policy: RateLimitPolicy) -> RateLimit {
+        self.policy = policy;
+        self
+    }
+}
+
+// what the rate limits did, part of PoolStats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    // jobs queued while the tokens on hand could not cover them and the jobs ahead
+    // (RateLimitPolicy::Wait)
+    pub delayed: u64,
+    // jobs refused (RateLimitPolicy::Reject)
+    pub rejected: u64,
+}
+
+// one RateLimit in action, lives under the queue lock
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    // starts full, the first burst goes through right away
+    pub(crate) fn new(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> RateLimitPolicy {
+        self.limit.policy
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.refilled_at = now;
+    }
+
+    // whole tokens on hand
+    pub(crate) fn available(&mut self, now: Instant) -> usize {
+        self.refill(now);
+        self.tokens as usize
+    }
+
+    pub(crate) fn take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    // how long until the next whole token, zero if there is one already
+    pub(crate) fn next_token(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((1.0 - self.tokens) / self.limit.per_second)
+    }
+}
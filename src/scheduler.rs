@@ -0,0 +1,188 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use crate::{queue::JobQueue, QueuedJob};
+
+// how workers find their next job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheduler {
+    // every worker takes jobs from the one shared queue
+    #[default]
+    SharedQueue,
+    // every worker keeps a local deque, fed in batches from the shared queue (the injector),
+    // and steals from the other workers when it runs dry
+    WorkStealing,
+}
+
+// jobs a worker pulls from the injector at once, the rest can be stolen by the others
+const MAX_BATCH: usize = 32;
+
+pub(crate) struct Stealing {
+    locals: RwLock<Vec<Arc<LocalQueue>>>,
+    // jobs sitting in any local deque, lets an idle worker know there is something to steal
+    stealable: AtomicUsize,
+}
+
+pub(crate) struct LocalQueue {
+    jobs: Mutex<VecDeque<QueuedJob>>,
+}
+
+thread_local! {
+    // the local deque of the worker running on this thread, together with the address of the
+    // pool it belongs to (a job may submit to a different pool)
+    static LOCAL: RefCell<Option<(usize, Arc<LocalQueue>)>> = const { RefCell::new(None) };
+}
+
+impl Stealing {
+    pub(crate) fn new() -> Stealing {
+        Stealing {
+            locals: RwLock::new(Vec::new()),
+            stealable: AtomicUsize::new(0),
+        }
+    }
+
+    fn key(&self) -> usize {
+        self as *const Stealing as usize
+    }
+
+    // called by a worker thread when it starts
+    pub(crate) fn register(&self) -> Arc<LocalQueue> {
+        let local = Arc::new(LocalQueue {
+            jobs: Mutex::new(VecDeque::new()),
+        });
+        self.locals.write().unwrap().push(Arc::clone(&local));
+        LOCAL.with(|current| *current.borrow_mut() = Some((self.key(), Arc::clone(&local))));
+
+        local
+    }
+
+    // called by a worker thread when it exits, leftover jobs go back to the injector
+    pub(crate) fn unregister(&self, local: &Arc<LocalQueue>, queue: &JobQueue) {
+        LOCAL.with(|current| *current.borrow_mut() = None);
+        self.locals
+            .write()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, local));
+
+        let mut jobs = local.jobs.lock().unwrap();
+        self.stealable.fetch_sub(jobs.len(), Ordering::SeqCst);
+        for job in jobs.drain(..) {
+            queue.requeue(job);
+        }
+    }
+
+    // the local deque of the worker running on this thread, None -> not one of our workers
+    pub(crate) fn current(&self) -> Option<Arc<LocalQueue>> {
+        let key = self.key();
+        LOCAL.with(|current| match &*current.borrow() {
+            Some((owner, local)) if *owner == key => Some(Arc::clone(local)),
+            _ => None,
+        })
+    }
+
+    // a job submitted from one of our own workers stays on that worker
+    pub(crate) fn push_local(&self, local: &LocalQueue, job: QueuedJob, queue: &JobQueue) {
+        local.jobs.lock().unwrap().push_back(job);
+        self.stealable.fetch_add(1, Ordering::SeqCst);
+        // somebody may be asleep on the injector with nothing to do
+        queue.wake_idle();
+    }
+
+    // drop the jobs in every local deque, returns how many there were
+    pub(crate) fn clear(&self) -> usize {
+        let mut count = 0;
+
+        for local in self.locals.read().unwrap().iter() {
+            let mut jobs = local.jobs.lock().unwrap();
+            count += jobs.len();
+            self.stealable.fetch_sub(jobs.len(), Ordering::SeqCst);
+            jobs.clear();
+        }
+
+        count
+    }
+
+    // jobs waiting in local deques per named queue, `queues` is how many there are
+    pub(crate) fn queued_by_queue(&self, queues: usize) -> Vec<usize> {
+        let mut counts = vec![0; queues];
+
+        for local in self.locals.read().unwrap().iter() {
+            for job in local.jobs.lock().unwrap().iter() {
+                counts[job.queue] += 1;
+            }
+        }
+
+        counts
+    }
+
+    // jobs waiting in local deques
+    pub(crate) fn len(&self) -> usize {
+        self.stealable.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn has_stealable(&self) -> bool {
+        self.stealable.load(Ordering::SeqCst) > 0
+    }
+
+    // local deque first, then a batch from the injector, then steal half of someone else's deque
+    pub(crate) fn find_job(&self, local: &LocalQueue, queue: &JobQueue) -> Option<QueuedJob> {
+        if let Some(job) = self.pop_local(local) {
+            return Some(job);
+        }
+
+        let mut batch = queue.try_pop_batch(MAX_BATCH);
+        if let Some(job) = batch.pop_front() {
+            self.keep(local, batch);
+            return Some(job);
+        }
+
+        if !self.has_stealable() {
+            return None;
+        }
+
+        let locals = self.locals.read().unwrap();
+        for victim in locals.iter() {
+            if std::ptr::eq(Arc::as_ptr(victim), local) {
+                continue;
+            }
+
+            let mut stolen = {
+                let mut jobs = victim.jobs.lock().unwrap();
+                let keep = jobs.len() / 2;
+                jobs.split_off(keep)
+            };
+            if let Some(job) = stolen.pop_front() {
+                self.stealable.fetch_sub(1, Ordering::SeqCst);
+                // the rest only moves from one deque to another -> still stealable
+                local.jobs.lock().unwrap().extend(stolen);
+                return Some(job);
+            }
+        }
+
+        None
+    }
+
+    fn pop_local(&self, local: &LocalQueue) -> Option<QueuedJob> {
+        let job = local.jobs.lock().unwrap().pop_front();
+        if job.is_some() {
+            self.stealable.fetch_sub(1, Ordering::SeqCst);
+        }
+        job
+    }
+
+    fn keep(&self, local: &LocalQueue, jobs: VecDeque<QueuedJob>) {
+        if jobs.is_empty() {
+            return;
+        }
+
+        let count = jobs.len();
+        local.jobs.lock().unwrap().extend(jobs);
+        self.stealable.fetch_add(count, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    any::Any,
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+};
+
+use crate::{Job, Priority, Shared};
+
+// handed to the closure passed to ThreadPool::scope, see Scope::spawn
+// 'scope -> how long the scope lives, jobs can not outlive it
+// 'env -> how long the borrowed data lives, always outlives 'scope
+pub struct Scope<'scope, 'env: 'scope> {
+    shared: Arc<Shared>,
+    state: Arc<ScopeState>,
+    // invariant over both lifetimes, same as std::thread::Scope
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+// what the jobs of one scope report back to the thread waiting in ThreadPool::scope
+struct ScopeState {
+    // spawned jobs that have not finished (or been dropped) yet
+    pending: Mutex<usize>,
+    all_done: Condvar,
+    // the first panic of a job, re-raised on the caller once everything finished
+    panic: Mutex<Option<Box<dyn Any + Send + 'static>>>,
+}
+
+impl ScopeState {
+    fn record_panic(&self, payload: Box<dyn Any + Send + 'static>) {
+        let mut panic = self.panic.lock().unwrap();
+        if panic.is_none() {
+            *panic = Some(payload);
+        }
+    }
+
+    fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.all_done.wait(pending).unwrap();
+        }
+    }
+}
+
+// owns the closure on its way through the queue, dropping it counts the job as finished
+// whether it ran or not, so the scope never waits for a job that is gone
+struct ScopedJob<F> {
+    f: Option<F>,
+    state: Arc<ScopeState>,
+}
+
+impl<F: FnOnce()> ScopedJob<F> {
+    fn run(mut self, shared: &Shared) {
+        let f = self.f.take().unwrap();
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            shared.caught_panic(payload.as_ref());
+            self.state.record_panic(payload);
+        }
+    }
+}
+
+impl<F> Drop for ScopedJob<F> {
+    fn drop(&mut self) {
+        // still here -> a worker threw the job away (e.g. the pool got poisoned meanwhile)
+        if let Some(f) = self.f.take() {
+            drop(f);
+            self.state
+                .record_panic(Box::new("scoped job was dropped before it ran"));
+        }
+
+        // the closure (and everything it borrowed) is gone before the scope may return
+        let mut pending = self.state.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.state.all_done.notify_all();
+        }
+    }
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    pub(crate) fn run<F, T>(shared: &Arc<Shared>, f: F) -> T
+    where
+        F: for<'s> FnOnce(&'s Scope<'s, 'env>) -> T,
+    {
+        let scope = Scope {
+            shared: Arc::clone(shared),
+            state: Arc::new(ScopeState {
+                pending: Mutex::new(0),
+                all_done: Condvar::new(),
+                panic: Mutex::new(None),
+            }),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+
+        // even when f panics the jobs it spawned keep borrowing from 'env -> wait for them first
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.state.wait();
+
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(value) => match scope.state.panic.lock().unwrap().take() {
+                Some(payload) => panic::resume_unwind(payload),
+                None => value,
+            },
+        }
+    }
+
+    // run f on a worker, f may borrow anything that outlives the scope
+    // a job the pool does not accept (full queue, poisoned or shut down) runs right here instead
+    pub fn spawn<F>(&'scope self, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+
+        let job = ScopedJob {
+            f: Some(f),
+            state: Arc::clone(&self.state),
+        };
+        let shared = Arc::clone(&self.shared);
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || job.run(&shared));
+        // SAFETY: the queue wants 'static jobs, but Scope::run does not return before every
+        // ScopedJob has been dropped, and the closure is dropped before the job counts as done
+        let job: Job = unsafe { mem::transmute(job) };
+
+        if let Err((_, job)) = self.shared.push_job(Priority::Normal, job, None) {
+            job();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{handle, tests, OverflowPolicy, ThreadPool, ThreadPoolBuilder};
+
+    // size 1 pool with a full queue of capacity 1: the worker waits for the sender to be used
+    // (or dropped), the queue holds one job
+    fn busy_pool(policy: OverflowPolicy) -> (ThreadPool, mpsc::Sender<()>) {
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .queue_capacity(1)
+            .overflow_policy(policy)
+            .build()
+            .unwrap();
+
+        let release = tests::block_worker(&pool);
+        pool.execute(|| {});
+
+        (pool, release)
+    }
+
+    #[test]
+    fn jobs_borrow_a_local_slice() {
+        let pool = ThreadPool::new(4);
+        let mut data: Vec<u32> = (0..1000).rev().collect();
+
+        pool.scope(|s| {
+            for chunk in data.chunks_mut(100) {
+                s.spawn(move || chunk.sort());
+            }
+        });
+
+        assert!(data.chunks(100).all(|chunk| chunk.is_sorted()));
+    }
+
+    #[test]
+    fn a_panic_is_raised_after_every_job_finished() {
+        let pool = ThreadPool::new(2);
+        let finished = AtomicUsize::new(0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.spawn(|| panic!("boom"));
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        thread::sleep(Duration::from_millis(20));
+                        finished.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+            })
+        }));
+
+        let payload = result.unwrap_err();
+        assert_eq!(handle::payload_message(payload.as_ref()), Some("boom"));
+        assert_eq!(finished.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn a_refused_job_runs_inline() {
+        let (pool, release) = busy_pool(OverflowPolicy::Reject);
+
+        let caller = thread::current().id();
+        let mut ran_on = None;
+        pool.scope(|s| {
+            s.spawn(|| ran_on = Some(thread::current().id()));
+        });
+
+        assert_eq!(ran_on, Some(caller));
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn a_dropped_job_releases_the_scope() {
+        let (pool, release) = busy_pool(OverflowPolicy::DropOldest);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                // takes the slot of the queued job
+                s.spawn(|| {});
+                // and loses it to this one
+                pool.execute(|| {});
+            })
+        }));
+
+        let payload = result.unwrap_err();
+        assert_eq!(
+            handle::payload_message(payload.as_ref()),
+            Some("scoped job was dropped before it ran")
+        );
+        release.send(()).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+// how ThreadPool::shutdown treats the jobs still waiting in the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    // run every queued job, then stop (what Drop does)
+    Drain,
+    // let running jobs finish, throw the queued ones away
+    DiscardQueued,
+    // drain, but stop waiting after the timeout: jobs still queued then are thrown away and
+    // workers still busy are left running on their own
+    Deadline(Duration),
+}
+
+// what ThreadPool::shutdown did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    // jobs that finished (or panicked) while shutting down, including the ones already running
+    pub completed: u64,
+    // queued jobs dropped without running
+    pub discarded: usize,
+    // ids of workers that had not exited when the deadline passed
+    pub unfinished_workers: Vec<usize>,
+}
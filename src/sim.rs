@@ -0,0 +1,207 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{Clock, Executor, Job, VirtualClock};
+
+// deterministic stand-in for a ThreadPool in tests: jobs run one at a time on the thread that
+// calls step / run_until_idle / advance, picked from the ready ones by a seeded generator,
+// and time is a VirtualClock that only moves when the test (or a job sleeping on it) says so
+// the same seed and the same submissions always give the same order -> a failing seed replays
+// let sim = Simulation::new(42);
+// sim.execute(|| ...);
+// sim.advance(Duration::from_secs(5));
+// assert_eq!(sim.history(), [...]);
+#[derive(Clone)]
+pub struct Simulation {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    seed: u64,
+    clock: VirtualClock,
+    state: Mutex<SimState>,
+}
+
+struct SimState {
+    rng: SplitMix64,
+    // submitted and not run yet, in no particular order: the generator picks
+    ready: Vec<(u64, Job)>,
+    // execute_after jobs waiting for the clock
+    timers: Vec<Timer>,
+    next_id: u64,
+    // job ids in the order they ran
+    history: Vec<u64>,
+}
+
+struct Timer {
+    due: Duration,
+    id: u64,
+    job: Job,
+}
+
+// tiny and good enough to shuffle jobs, and the same on every platform
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl SimState {
+    // timers that are due join the ready jobs, oldest due first
+    fn release_timers(&mut self, now: Duration) {
+        self.timers.sort_by_key(|timer| (timer.due, timer.id));
+        let due = self
+            .timers
+            .iter()
+            .take_while(|timer| timer.due <= now)
+            .count();
+        let released: Vec<Timer> = self.timers.drain(..due).collect();
+        self.ready
+            .extend(released.into_iter().map(|timer| (timer.id, timer.job)));
+    }
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Simulation {
+        Simulation {
+            inner: Arc::new(Inner {
+                seed,
+                clock: VirtualClock::new(),
+                state: Mutex::new(SimState {
+                    rng: SplitMix64(seed),
+                    ready: Vec::new(),
+                    timers: Vec::new(),
+                    next_id: 0,
+                    history: Vec::new(),
+                }),
+            }),
+        }
+    }
+
+    // print it when a test fails, Simulation::new(seed) replays the same order
+    pub fn seed(&self) -> u64 {
+        self.inner.seed
+    }
+
+    // hand a clone to the code under test in place of a SystemClock
+    pub fn clock(&self) -> VirtualClock {
+        self.inner.clock.clone()
+    }
+
+    pub fn now(&self) -> Duration {
+        self.inner.clock.now()
+    }
+
+    // returns the job id that shows up in history
+    pub fn submit<F>(&self, f: F) -> u64
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.inner.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.ready.push((id, Box::new(f)));
+        id
+    }
+
+    // f becomes ready once the virtual clock reaches now + delay
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> u64
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let due = self.now() + delay;
+        let mut state = self.inner.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.timers.push(Timer {
+            due,
+            id,
+            job: Box::new(f),
+        });
+        id
+    }
+
+    // run one ready job, false -> nothing was ready
+    // the job runs without the lock held, it may submit more jobs or sleep on the clock
+    pub fn step(&self) -> bool {
+        let job = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.release_timers(self.inner.clock.now());
+            if state.ready.is_empty() {
+                return false;
+            }
+
+            let pick = (state.rng.next() % state.ready.len() as u64) as usize;
+            let (id, job) = state.ready.swap_remove(pick);
+            state.history.push(id);
+            job
+        };
+
+        job();
+        true
+    }
+
+    // run ready jobs until there are none left, time does not move (unless a job sleeps)
+    // returns how many ran
+    pub fn run_until_idle(&self) -> usize {
+        let mut ran = 0;
+        while self.step() {
+            ran += 1;
+        }
+        ran
+    }
+
+    // move the clock forward by duration, stopping at every timer on the way to run what
+    // became ready; returns how many jobs ran
+    pub fn advance(&self, duration: Duration) -> usize {
+        let target = self.now() + duration;
+        let mut ran = self.run_until_idle();
+
+        loop {
+            let next_due = {
+                let state = self.inner.state.lock().unwrap();
+                state.timers.iter().map(|timer| timer.due).min()
+            };
+            match next_due {
+                Some(due) if due <= target => {
+                    self.inner.clock.advance_to(due);
+                    ran += self.run_until_idle();
+                }
+                _ => break,
+            }
+        }
+
+        self.inner.clock.advance_to(target);
+        ran + self.run_until_idle()
+    }
+
+    // jobs still waiting, timers included
+    pub fn pending(&self) -> usize {
+        let state = self.inner.state.lock().unwrap();
+        state.ready.len() + state.timers.len()
+    }
+
+    // ids (from submit / execute_after) in the order the jobs ran
+    pub fn history(&self) -> Vec<u64> {
+        self.inner.state.lock().unwrap().history.clone()
+    }
+}
+
+// execute only queues the job, nothing runs until the test calls step, run_until_idle or
+// advance; priorities are ignored, the seed alone decides the order
+impl Executor for Simulation {
+    fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(f);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc, Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::{JobHandle, JoinError, Priority, Shared};
+
+// where a task is between polls, see Task::schedule and Task::run
+const IDLE: u8 = 0;
+// a job that polls it sits in the queue
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+// woken while running -> poll once more afterwards
+const NOTIFIED: u8 = 3;
+const DONE: u8 = 4;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// a future spawned with ThreadPool::spawn_future
+// every poll is one job on the pool, its Waker queues the next one
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    state: AtomicU8,
+    // Weak -> a waker kept somewhere does not keep the pool's state alive
+    shared: Weak<Shared>,
+}
+
+pub(crate) fn spawn<F>(shared: &Arc<Shared>, future: F) -> JobHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    // same as submit: the result, or the panic, goes back through a channel
+    let (sender, receiver) = mpsc::channel();
+    let weak = Arc::downgrade(shared);
+
+    let mut future = Box::pin(future);
+    let future = std::future::poll_fn(move |cx| {
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(value)) => {
+                let _ = sender.send(Ok(value));
+                Poll::Ready(())
+            }
+            Err(payload) => {
+                if let Some(shared) = weak.upgrade() {
+                    shared.caught_panic(payload.as_ref());
+                }
+                let _ = sender.send(Err(JoinError::Panicked(payload)));
+                Poll::Ready(())
+            }
+        }
+    });
+
+    let task = Arc::new(Task {
+        future: Mutex::new(Some(Box::pin(future))),
+        state: AtomicU8::new(SCHEDULED),
+        shared: Arc::downgrade(shared),
+    });
+    task.enqueue();
+
+    JobHandle::new(receiver)
+}
+
+impl Task {
+    // called by the waker, from any thread
+    fn schedule(self: &Arc<Task>) {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                // already queued, already noted or finished
+                _ => return,
+            };
+            match self
+                .state
+                .compare_exchange(state, next, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) if next == SCHEDULED => return self.enqueue(),
+                Ok(_) => return,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    fn enqueue(self: &Arc<Task>) {
+        let task = Arc::clone(self);
+        let pushed = match self.shared.upgrade() {
+            Some(shared) => shared
+                .push_job(Priority::Normal, Box::new(move || task.run()), None)
+                .is_ok(),
+            None => false,
+        };
+
+        // nobody will poll the future again: drop it, the handle reports JoinError::Cancelled
+        if !pushed {
+            self.state.store(DONE, Ordering::SeqCst);
+            *self.future.lock().unwrap() = None;
+        }
+    }
+
+    fn run(self: Arc<Task>) {
+        self.state.store(RUNNING, Ordering::SeqCst);
+
+        let waker = Waker::from(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = self.future.lock().unwrap();
+        let ready = match future.as_mut() {
+            Some(future) => future.as_mut().poll(&mut cx).is_ready(),
+            None => return,
+        };
+        if ready {
+            *future = None;
+            self.state.store(DONE, Ordering::SeqCst);
+            return;
+        }
+        drop(future);
+
+        // woken while we polled -> poll again, through the queue so other jobs get a turn
+        if self
+            .state
+            .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            self.state.store(SCHEDULED, Ordering::SeqCst);
+            self.enqueue();
+        }
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Task>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Task>) {
+        self.schedule();
+    }
+}
+
+// wakes the thread parked in block_on
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<ThreadWaker>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<ThreadWaker>) {
+        self.0.unpark();
+    }
+}
+
+// run a future to completion on the calling thread, parking it while the future waits
+// for driving a library's future from plain synchronous code, no pool involved
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return value;
+        }
+        // a wake that came in before we got here leaves the token set, park returns at once
+        thread::park();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        task::{Poll, Waker},
+        thread,
+        time::Duration,
+    };
+
+    use super::block_on;
+    use crate::{JoinError, ThreadPool};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn a_wake_while_running_polls_again() {
+        let pool = ThreadPool::new(1);
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        let future_polls = Arc::clone(&polls);
+        let handle = pool.spawn_future(future::poll_fn(move |cx| {
+            if future_polls.fetch_add(1, Ordering::SeqCst) == 1 {
+                return Poll::Ready("done");
+            }
+            // woken from another thread before this poll returns, the task is still RUNNING
+            let waker = cx.waker().clone();
+            thread::spawn(move || waker.wake()).join().unwrap();
+            Poll::Pending
+        }));
+
+        assert_eq!(handle.join_timeout(TIMEOUT).unwrap().unwrap(), "done");
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn a_panic_in_poll_is_reported() {
+        let pool = ThreadPool::new(1);
+
+        let handle = pool.spawn_future(async { panic!("boom") });
+
+        let result: Result<(), JoinError> = handle.join_timeout(TIMEOUT).unwrap();
+        assert!(matches!(result, Err(JoinError::Panicked(_))));
+    }
+
+    #[test]
+    fn a_pending_task_is_cancelled_when_the_pool_goes_away() {
+        let pool = ThreadPool::new(1);
+        let (sender, polled) = mpsc::channel::<Waker>();
+
+        let handle = pool.spawn_future(future::poll_fn(move |cx| {
+            let _ = sender.send(cx.waker().clone());
+            Poll::<()>::Pending
+        }));
+        let waker = polled.recv_timeout(TIMEOUT).unwrap();
+        drop(pool);
+        // nobody is left to poll it: the wake drops the future instead
+        waker.wake();
+
+        assert!(matches!(
+            handle.join_timeout(TIMEOUT),
+            Some(Err(JoinError::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn block_on_parks_until_woken() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let mut started = false;
+
+        let value = block_on(future::poll_fn(|cx| {
+            if ready.load(Ordering::SeqCst) {
+                return Poll::Ready(7);
+            }
+            if !started {
+                started = true;
+                let (ready, waker) = (Arc::clone(&ready), cx.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    ready.store(true, Ordering::SeqCst);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }));
+
+        assert_eq!(value, 7);
+        assert_eq!(block_on(async { 5 }), 5);
+    }
+}
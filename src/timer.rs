@@ -0,0 +1,375 @@
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Job, Priority, Shared};
+
+// returned by execute_after / execute_every
+#[derive(Debug, Clone)]
+pub struct TimerHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TimerHandle {
+    // a run that is already queued is skipped as well, a running one finishes
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// one thread sleeping until the next entry is due, then feeding it into the pool's queue
+pub(crate) struct Timer {
+    inner: Arc<TimerInner>,
+    thread: thread::JoinHandle<()>,
+}
+
+struct TimerInner {
+    state: Mutex<TimerState>,
+    // signalled when an earlier entry is added or the timer stops
+    changed: Condvar,
+}
+
+struct TimerState {
+    entries: BinaryHeap<Entry>,
+    // keeps entries with the same due time in the order they were scheduled
+    next_seq: u64,
+    stopped: bool,
+}
+
+struct Entry {
+    due: Instant,
+    seq: u64,
+    task: Task,
+    cancelled: Arc<AtomicBool>,
+}
+
+enum Task {
+    Once(Job),
+    Every {
+        f: Arc<dyn Fn() + Send + Sync>,
+        interval: Duration,
+        // set while a run is queued or running
+        in_flight: Arc<AtomicBool>,
+    },
+}
+
+// BinaryHeap is a max-heap -> reverse the order so the earliest entry is on top
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.due, other.seq).cmp(&(self.due, self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+// clears in_flight when the queued run finishes, panics or is dropped without running
+struct InFlight(Arc<AtomicBool>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Timer {
+    pub(crate) fn start(shared: Arc<Shared>) -> io::Result<Timer> {
+        let inner = Arc::new(TimerInner {
+            state: Mutex::new(TimerState {
+                entries: BinaryHeap::new(),
+                next_seq: 0,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let thread_inner = Arc::clone(&inner);
+        let thread = shared
+            .thread_options
+            .builder("timer")
+            .spawn(move || thread_inner.run(&shared))?;
+
+        Ok(Timer { inner, thread })
+    }
+
+    pub(crate) fn schedule_once(&self, delay: Duration, job: Job) -> TimerHandle {
+        self.schedule(Instant::now() + delay, Task::Once(job))
+    }
+
+    pub(crate) fn schedule_every(
+        &self,
+        interval: Duration,
+        f: Arc<dyn Fn() + Send + Sync>,
+    ) -> TimerHandle {
+        let task = Task::Every {
+            f,
+            interval,
+            in_flight: Arc::new(AtomicBool::new(false)),
+        };
+        self.schedule(Instant::now() + interval, task)
+    }
+
+    fn schedule(&self, due: Instant, task: Task) -> TimerHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut state = self.inner.state.lock().unwrap();
+
+        // a stopped timer never runs anything
+        if state.stopped {
+            cancelled.store(true, Ordering::SeqCst);
+        } else {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.entries.push(Entry {
+                due,
+                seq,
+                task,
+                cancelled: Arc::clone(&cancelled),
+            });
+            self.inner.changed.notify_one();
+        }
+
+        TimerHandle { cancelled }
+    }
+
+    // cancel every pending entry and wait for the timer thread to exit
+    pub(crate) fn stop(self) {
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            state.stopped = true;
+            for entry in state.entries.drain() {
+                entry.cancelled.store(true, Ordering::SeqCst);
+            }
+            self.inner.changed.notify_one();
+        }
+
+        let _ = self.thread.join();
+    }
+}
+
+impl TimerInner {
+    fn run(&self, shared: &Arc<Shared>) {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if state.stopped {
+                break;
+            }
+
+            let now = Instant::now();
+            let due = match state.entries.peek() {
+                Some(entry) => entry.due,
+                None => {
+                    state = self.changed.wait(state).unwrap();
+                    continue;
+                }
+            };
+            if due > now {
+                state = self.changed.wait_timeout(state, due - now).unwrap().0;
+                continue;
+            }
+
+            let entry = state.entries.pop().unwrap();
+            if entry.cancelled.load(Ordering::SeqCst) {
+                continue;
+            }
+
+            // pushing may block on a full queue -> do not hold the timer lock meanwhile
+            drop(state);
+            let again = fire(shared, entry, now);
+            state = self.state.lock().unwrap();
+
+            if let Some(mut entry) = again {
+                if !state.stopped {
+                    entry.seq = state.next_seq;
+                    state.next_seq += 1;
+                    state.entries.push(entry);
+                }
+            }
+        }
+    }
+}
+
+// queue the run of a due entry, Some -> the periodic entry to schedule again
+fn fire(shared: &Arc<Shared>, entry: Entry, now: Instant) -> Option<Entry> {
+    let cancelled = Arc::clone(&entry.cancelled);
+
+    match entry.task {
+        Task::Once(job) => {
+            let _ = shared.push_job(
+                Priority::Normal,
+                Box::new(move || {
+                    if !cancelled.load(Ordering::SeqCst) {
+                        job();
+                    }
+                }),
+                None,
+            );
+            None
+        }
+        Task::Every {
+            ref f,
+            interval,
+            ref in_flight,
+        } => {
+            // the previous run has not finished yet -> skip this tick
+            if !in_flight.swap(true, Ordering::SeqCst) {
+                let f = Arc::clone(f);
+                let guard = InFlight(Arc::clone(in_flight));
+                let _ = shared.push_job(
+                    Priority::Normal,
+                    Box::new(move || {
+                        let _guard = guard;
+                        if !cancelled.load(Ordering::SeqCst) {
+                            f();
+                        }
+                    }),
+                    None,
+                );
+            }
+
+            // fixed rate, but ticks missed while we were behind are skipped
+            let mut due = entry.due + interval;
+            if due <= now {
+                due = now + interval;
+            }
+            Some(Entry { due, ..entry })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::ThreadPool;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn execute_after_waits_for_the_delay() {
+        let pool = ThreadPool::new(1);
+        let (sender, receiver) = mpsc::channel();
+
+        let scheduled = Instant::now();
+        pool.execute_after(Duration::from_millis(50), move || {
+            sender.send(Instant::now()).unwrap();
+        });
+
+        let ran = receiver.recv_timeout(TIMEOUT).unwrap();
+        assert!(ran - scheduled >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn execute_every_runs_until_cancelled() {
+        let pool = ThreadPool::new(1);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let timer_runs = Arc::clone(&runs);
+        let handle = pool.execute_every(Duration::from_millis(5), move || {
+            timer_runs.fetch_add(1, Ordering::SeqCst);
+            let _ = sender.send(());
+        });
+        for _ in 0..3 {
+            receiver.recv_timeout(TIMEOUT).unwrap();
+        }
+        handle.cancel();
+        // a run that was already going when cancel returned may still finish
+        thread::sleep(Duration::from_millis(20));
+        let after_cancel = runs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+
+        assert!(handle.is_cancelled());
+        assert_eq!(runs.load(Ordering::SeqCst), after_cancel);
+    }
+
+    #[test]
+    fn a_cancelled_timer_never_runs() {
+        let pool = ThreadPool::new(1);
+        let ran = Arc::new(AtomicBool::new(false));
+
+        let job_ran = Arc::clone(&ran);
+        let handle = pool.execute_after(Duration::from_millis(20), move || {
+            job_ran.store(true, Ordering::SeqCst);
+        });
+        handle.cancel();
+        thread::sleep(Duration::from_millis(60));
+
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn a_tick_is_skipped_while_the_previous_run_is_in_flight() {
+        // two workers: without the skip a second run would start next to the slow one
+        let pool = ThreadPool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let (timer_running, timer_most, timer_runs) = (
+            Arc::clone(&running),
+            Arc::clone(&most_running),
+            Arc::clone(&runs),
+        );
+        let handle = pool.execute_every(Duration::from_millis(5), move || {
+            let now = timer_running.fetch_add(1, Ordering::SeqCst) + 1;
+            timer_most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(30));
+            timer_running.fetch_sub(1, Ordering::SeqCst);
+            timer_runs.fetch_add(1, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(150));
+        handle.cancel();
+
+        assert_eq!(most_running.load(Ordering::SeqCst), 1);
+        // one every 5ms would be 30
+        assert!(runs.load(Ordering::SeqCst) <= 6);
+    }
+
+    #[test]
+    fn drop_cancels_pending_timers() {
+        let pool = ThreadPool::new(1);
+        let ran = Arc::new(AtomicBool::new(false));
+
+        let job_ran = Arc::clone(&ran);
+        let handle = pool.execute_after(Duration::from_secs(60), move || {
+            job_ran.store(true, Ordering::SeqCst);
+        });
+        let dropped = Instant::now();
+        drop(pool);
+
+        assert!(dropped.elapsed() < TIMEOUT);
+        assert!(handle.is_cancelled());
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::Ordering, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{config::StuckJobHook, Level, Shared};
+
+// the watchdog never looks more often than this, however short the deadline
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+// what the workers tell the watchdog, lives in Shared so Worker::run can reach it
+pub(crate) struct JobWatch {
+    deadline: Duration,
+    hook: Option<StuckJobHook>,
+    // worker id -> the job it is running right now
+    running: Mutex<HashMap<usize, Running>>,
+}
+
+struct Running {
+    job: u64,
+    started: Instant,
+    // every stuck job is reported once, not on every check
+    reported: bool,
+}
+
+impl JobWatch {
+    pub(crate) fn new(deadline: Duration, hook: Option<StuckJobHook>) -> JobWatch {
+        JobWatch {
+            deadline,
+            hook,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn started(&self, worker: usize, job: u64) {
+        self.running.lock().unwrap().insert(
+            worker,
+            Running {
+                job,
+                started: Instant::now(),
+                reported: false,
+            },
+        );
+    }
+
+    pub(crate) fn finished(&self, worker: usize) {
+        self.running.lock().unwrap().remove(&worker);
+    }
+
+    // (worker, job, running for) of every job past the deadline that was not reported yet
+    fn overdue(&self) -> Vec<(usize, u64, Duration)> {
+        let now = Instant::now();
+        let mut running = self.running.lock().unwrap();
+
+        running
+            .iter_mut()
+            .filter(|(_, running)| !running.reported)
+            .filter(|(_, running)| now.duration_since(running.started) > self.deadline)
+            .map(|(worker, running)| {
+                running.reported = true;
+                (*worker, running.job, now.duration_since(running.started))
+            })
+            .collect()
+    }
+}
+
+// one thread checking the running jobs a few times per deadline
+pub(crate) struct Watchdog {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Watchdog {
+    // only called when shared.watch is Some
+    pub(crate) fn start(shared: Arc<Shared>) -> io::Result<Watchdog> {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+
+        let thread_stop = Arc::clone(&stop);
+        let thread = shared
+            .thread_options
+            .builder("watchdog")
+            .spawn(move || run(&shared, &thread_stop))?;
+
+        Ok(Watchdog { stop, thread })
+    }
+
+    pub(crate) fn stop(self) {
+        let (stopped, changed) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        changed.notify_all();
+        let _ = self.thread.join();
+    }
+}
+
+fn run(shared: &Shared, stop: &(Mutex<bool>, Condvar)) {
+    let watch = shared.watch.as_ref().unwrap();
+    let interval = (watch.deadline / 4).max(MIN_CHECK_INTERVAL);
+    let (stopped, changed) = stop;
+
+    let mut is_stopped = stopped.lock().unwrap();
+    loop {
+        is_stopped = changed.wait_timeout(is_stopped, interval).unwrap().0;
+        if *is_stopped {
+            break;
+        }
+
+        for (worker, job, running) in watch.overdue() {
+            shared.metrics.stuck.fetch_add(1, Ordering::Relaxed);
+            shared.log(
+                Level::Warn,
+                "job exceeded deadline",
+                &[
+                    ("worker", worker.into()),
+                    ("job", job.into()),
+                    ("running_ms", (running.as_millis() as u64).into()),
+                ],
+            );
+
+            if let Some(hook) = &watch.hook {
+                // a panicking hook must not take the watchdog down with it
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(worker, running)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use crate::ThreadPoolBuilder;
+
+    #[test]
+    fn a_stuck_job_is_counted_and_reported_once() {
+        let (sender, reported) = mpsc::channel();
+        let pool = ThreadPoolBuilder::new()
+            .size(1)
+            .job_deadline(Duration::from_millis(20))
+            .on_stuck_job(move |worker, running| {
+                let _ = sender.send((worker, running));
+            })
+            .build()
+            .unwrap();
+
+        // stuck for several checks of the watchdog
+        pool.submit(|| thread::sleep(Duration::from_millis(150)))
+            .join()
+            .unwrap();
+
+        let (worker, running) = reported.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(worker, 0);
+        assert!(running > Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(50));
+        assert!(reported.try_recv().is_err());
+        assert_eq!(pool.stats().stuck, 1);
+    }
+}